@@ -0,0 +1,561 @@
+//!
+//! `#[derive(DomainModel)]` for [domain_keys](../domain_keys/index.html) models.  The derive
+//! implements `DomainModel` from attributes on the struct and its fields, so a domain needs no
+//! hand-written validation, views or key domain.
+//!
+//! The value type still needs `Serialize`, and `Deserialize` and `Clone` to be stored.
+//!
+//! Struct attributes, all optional:
+//!
+//! * `#[domain(prefix = "usr")]` : implement `KeyDomain` with the prefix and key models with a
+//!   `DomainId<Self>`
+//! * `#[domain(key = "TimeStampId")]` : key models with the key type; the default is `RouteId`
+//! * `#[domain(transitions = "path::to::fn")]` : the `fn() -> Transitions` for the domain
+//! * `#[domain(schema = 2)]` : the value's schema version; bump it when the serialized value
+//!   changes and register an upcaster, see `domain_keys::schema`
+//! * `#[domain(validate = "path::to::fn")]` : a `fn(&Model<Self, Self::Key>) -> Vec<ModelError>`
+//!   for checks that aren't field rules; its errors follow the field errors
+//!
+//! Field attributes:
+//!
+//! * `#[validate(...)]` : the rules for the field, checked in order; any of `required`, `email`,
+//!   `phone`, `length(min = 2, max = 40)`, `range(min = 18, max = 120)`, `pattern = "^[a-z]+$"`
+//!   and `one_of("a", "b")`.  Rules other than `required` skip `Option` fields that are `None`,
+//!   and `Vec` fields accept `required` and `length`.  An invalid `pattern` is a compile error;
+//!   a valid one is compiled once, on first use.
+//! * `#[view(list, public)]` : include the field in the named views; every view also includes
+//!   the key and status, and an `admin` view of the whole model is added unless a field names it
+//! * `#[sensitive]` : the field is a `domain_keys::redact::Sensitive`, e.g.,
+//!   `SensitiveEmail<String>`, so its mask hides it in `Debug` and in every view except the
+//!   generated `admin` view; any other field type is a compile error
+//!
+//! Every generated view except `admin` serializes with `domain_keys::redact::to_value`, so each
+//! `Sensitive` field in it is masked, marked or not.  Field paths are json pointers from the
+//! serialized field names, so `#[serde(rename = "...")]` is honored; container level
+//! `rename_all` is not.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::models::{DomainModel, Model};
+//! use domain_keys::redact::SensitiveEmail;
+//! use serde_derive::{Deserialize, Serialize};
+//!
+//! #[derive(Debug, Clone, Serialize, Deserialize, DomainModel)]
+//! #[domain(prefix = "usr")]
+//! struct User {
+//!     #[validate(required, length(min = 2, max = 40))]
+//!     #[view(list)]
+//!     name: String,
+//!     #[validate(required, email)]
+//!     #[view(list)]
+//!     #[sensitive]
+//!     email: SensitiveEmail<String>,
+//! }
+//!
+//! let user = User { name: "sam".to_string(), email: "sam@rcs.com".to_string().into() };
+//! assert_eq!(format!("{:?}", user), r#"User { name: "sam", email: "s***@rcs.com" }"#);
+//!
+//! let model = Model::new_domain(user);
+//! assert!(model.key.starts_with("usr_"));
+//! assert!(User::validate(&model).is_empty());
+//!
+//! let list = model.view("list").unwrap();
+//! assert_eq!(list["value"]["name"], "sam");
+//! assert_eq!(list["value"]["email"], "s***@rcs.com");
+//! assert_eq!(model.view("admin").unwrap()["value"]["email"], "sam@rcs.com");
+//! ```
+//!
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{quote, quote_spanned};
+use syn::meta::ParseNestedMeta;
+use syn::punctuated::Punctuated;
+use syn::{
+    parenthesized, parse_macro_input, Data, DeriveInput, Expr, Fields, LitInt, LitStr, Path, Token,
+    Type,
+};
+
+/// Derive `DomainModel`; see the crate docs for the attributes.
+#[proc_macro_derive(DomainModel, attributes(domain, validate, view, sensitive))]
+pub fn derive_domain_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+// the struct level #[domain(...)] settings
+#[derive(Default)]
+struct Domain {
+    prefix: Option<LitStr>,
+    key: Option<Path>,
+    transitions: Option<Path>,
+    validate: Option<Path>,
+    schema: Option<LitInt>,
+}
+
+// how a field's value is passed to the rules
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Plain,
+    Option,
+    Vec,
+}
+
+enum Rule {
+    Required,
+    Email,
+    Phone,
+    Length {
+        min: Option<Expr>,
+        max: Option<Expr>,
+    },
+    Range {
+        min: Expr,
+        max: Expr,
+    },
+    Pattern(LitStr),
+    OneOf(Vec<LitStr>),
+}
+
+struct Field {
+    ident: syn::Ident,
+    path: String,
+    shape: Shape,
+    ty_span: proc_macro2::Span,
+    rules: Vec<(Rule, proc_macro2::Span)>,
+    views: Vec<String>,
+    sensitive: bool,
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let domain = parse_domain(input)?;
+    let fields = parse_fields(input)?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut key_domain = TokenStream2::new();
+    let key = match (&domain.prefix, &domain.key) {
+        (Some(prefix), None) => {
+            key_domain = quote! {
+                impl #impl_generics ::domain_keys::keys::KeyDomain for #name #ty_generics #where_clause {
+                    const PREFIX: &'static str = #prefix;
+                }
+            };
+            quote!(::domain_keys::keys::DomainId<Self>)
+        }
+        (None, Some(key)) => quote!(#key),
+        (None, None) => quote!(::domain_keys::keys::RouteId),
+        (Some(prefix), Some(_)) => {
+            return Err(syn::Error::new(
+                prefix.span(),
+                "use either a key prefix or a key type, not both",
+            ))
+        }
+    };
+
+    let validate = expand_validate(&domain, &fields)?;
+    let views = expand_views(&fields);
+    let transitions = domain.transitions.as_ref().map(|path| {
+        quote! {
+            fn transitions() -> ::domain_keys::models::Transitions {
+                #path()
+            }
+        }
+    });
+
+    let schema = domain.schema.as_ref().map(|schema| {
+        quote! {
+            const SCHEMA: u16 = #schema;
+        }
+    });
+
+    Ok(quote! {
+        #key_domain
+
+        impl #impl_generics ::domain_keys::models::DomainModel for #name #ty_generics #where_clause {
+            type Key = #key;
+
+            #schema
+
+            #validate
+
+            #views
+
+            #transitions
+        }
+    })
+}
+
+fn parse_domain(input: &DeriveInput) -> syn::Result<Domain> {
+    let mut domain = Domain::default();
+
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("domain")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("prefix") {
+                let prefix: LitStr = meta.value()?.parse()?;
+                let value = prefix.value();
+                if value.is_empty() || !value.chars().all(|ch| ch.is_ascii_alphanumeric()) {
+                    return Err(syn::Error::new(
+                        prefix.span(),
+                        "the key prefix must be ascii letters and digits",
+                    ));
+                }
+                domain.prefix = Some(prefix);
+            } else if meta.path.is_ident("key") {
+                domain.key = Some(parse_path(&meta)?);
+            } else if meta.path.is_ident("transitions") {
+                domain.transitions = Some(parse_path(&meta)?);
+            } else if meta.path.is_ident("validate") {
+                domain.validate = Some(parse_path(&meta)?);
+            } else if meta.path.is_ident("schema") {
+                let schema: LitInt = meta.value()?.parse()?;
+                if schema.base10_parse::<u16>()? == 0 {
+                    return Err(syn::Error::new(schema.span(), "schema versions start at 1"));
+                }
+                domain.schema = Some(schema);
+            } else {
+                return Err(meta.error("unknown domain attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(domain)
+}
+
+// a path in a string, e.g., key = "TimeStampId", like serde's `with = "..."`
+fn parse_path(meta: &ParseNestedMeta) -> syn::Result<Path> {
+    let lit: LitStr = meta.value()?.parse()?;
+    lit.parse()
+}
+
+fn parse_fields(input: &DeriveInput) -> syn::Result<Vec<Field>> {
+    let named = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(named) => named,
+            _ => return Err(not_supported(input)),
+        },
+        _ => return Err(not_supported(input)),
+    };
+
+    let mut fields = Vec::new();
+    for field in &named.named {
+        let ident = field.ident.clone().expect("named fields have idents");
+        let mut name = ident.to_string();
+        let mut rules = Vec::new();
+        let mut views = Vec::new();
+        let mut sensitive = false;
+
+        for attr in &field.attrs {
+            if attr.path().is_ident("validate") {
+                attr.parse_nested_meta(|meta| {
+                    rules.push((parse_rule(&meta)?, meta.path.get_ident().unwrap().span()));
+                    Ok(())
+                })?;
+            } else if attr.path().is_ident("view") {
+                attr.parse_nested_meta(|meta| match meta.path.get_ident() {
+                    Some(view) => {
+                        views.push(view.to_string());
+                        Ok(())
+                    }
+                    None => Err(meta.error("expected a view name")),
+                })?;
+            } else if attr.path().is_ident("sensitive") {
+                attr.meta.require_path_only()?;
+                sensitive = true;
+            } else if attr.path().is_ident("serde") {
+                if let Some(rename) = serde_rename(attr)? {
+                    name = rename;
+                }
+            }
+        }
+
+        fields.push(Field {
+            ident,
+            path: format!("/{}", name.replace('~', "~0").replace('/', "~1")),
+            shape: shape(&field.ty),
+            ty_span: syn::spanned::Spanned::span(&field.ty),
+            rules,
+            views,
+            sensitive,
+        });
+    }
+
+    Ok(fields)
+}
+
+fn not_supported(input: &DeriveInput) -> syn::Error {
+    syn::Error::new(
+        input.ident.span(),
+        "DomainModel can only be derived for structs with named fields",
+    )
+}
+
+fn parse_rule(meta: &ParseNestedMeta) -> syn::Result<Rule> {
+    let rule = match meta.path.get_ident().map(|ident| ident.to_string()) {
+        Some(name) if name == "required" => Rule::Required,
+        Some(name) if name == "email" => Rule::Email,
+        Some(name) if name == "phone" => Rule::Phone,
+        Some(name) if name == "pattern" => {
+            let pattern: LitStr = meta.value()?.parse()?;
+            if regex::Regex::new(&pattern.value()).is_err() {
+                return Err(syn::Error::new(pattern.span(), "invalid regex pattern"));
+            }
+            Rule::Pattern(pattern)
+        }
+        Some(name) if name == "length" => {
+            let (mut min, mut max) = (None, None);
+            meta.parse_nested_meta(|bound| {
+                if bound.path.is_ident("min") {
+                    min = Some(bound.value()?.parse()?);
+                } else if bound.path.is_ident("max") {
+                    max = Some(bound.value()?.parse()?);
+                } else {
+                    return Err(bound.error("expected min or max"));
+                }
+                Ok(())
+            })?;
+            if min.is_none() && max.is_none() {
+                return Err(meta.error("length needs a min, a max or both"));
+            }
+            Rule::Length { min, max }
+        }
+        Some(name) if name == "range" => {
+            let (mut min, mut max) = (None, None);
+            meta.parse_nested_meta(|bound| {
+                if bound.path.is_ident("min") {
+                    min = Some(bound.value()?.parse()?);
+                } else if bound.path.is_ident("max") {
+                    max = Some(bound.value()?.parse()?);
+                } else {
+                    return Err(bound.error("expected min or max"));
+                }
+                Ok(())
+            })?;
+            match (min, max) {
+                (Some(min), Some(max)) => Rule::Range { min, max },
+                _ => return Err(meta.error("range needs a min and a max")),
+            }
+        }
+        Some(name) if name == "one_of" => {
+            let content;
+            parenthesized!(content in meta.input);
+            let choices = Punctuated::<LitStr, Token![,]>::parse_terminated(&content)?;
+            Rule::OneOf(choices.into_iter().collect())
+        }
+        _ => return Err(meta.error("unknown validation rule")),
+    };
+
+    Ok(rule)
+}
+
+// the field name from #[serde(rename = "...")], skipping every other serde setting
+fn serde_rename(attr: &syn::Attribute) -> syn::Result<Option<String>> {
+    let mut rename = None;
+    attr.parse_nested_meta(|meta| {
+        if meta.path.is_ident("rename") && meta.input.peek(Token![=]) {
+            let name: LitStr = meta.value()?.parse()?;
+            rename = Some(name.value());
+        } else if meta.input.peek(Token![=]) {
+            meta.value()?.parse::<Expr>()?;
+        } else if meta.input.peek(syn::token::Paren) {
+            let _content;
+            parenthesized!(_content in meta.input);
+        }
+        Ok(())
+    })?;
+
+    Ok(rename)
+}
+
+fn shape(ty: &Type) -> Shape {
+    let last = match ty {
+        Type::Path(path) => path.path.segments.last(),
+        _ => None,
+    };
+
+    match last {
+        Some(segment) if segment.ident == "Option" => Shape::Option,
+        Some(segment) if segment.ident == "Vec" => Shape::Vec,
+        _ => Shape::Plain,
+    }
+}
+
+fn expand_validate(domain: &Domain, fields: &[Field]) -> syn::Result<TokenStream2> {
+    let mut checks = Vec::new();
+    for field in fields {
+        for (rule, span) in &field.rules {
+            checks.push(expand_rule(field, rule, *span)?);
+        }
+    }
+
+    let custom = domain
+        .validate
+        .as_ref()
+        .map(|path| quote!(errors.extend(#path(model));));
+
+    let errors = if checks.is_empty() {
+        quote!(::std::vec::Vec::new())
+    } else {
+        quote! {
+            {
+                let value = &model.value;
+                ::domain_keys::validation::Validation::new()
+                    #(#checks)*
+                    .finish()
+                    .into_errors()
+            }
+        }
+    };
+
+    Ok(quote! {
+        fn validate(
+            model: &::domain_keys::models::Model<Self, Self::Key>,
+        ) -> ::std::vec::Vec<::domain_keys::models::ModelError> {
+            #[allow(unused_mut)]
+            let mut errors: ::std::vec::Vec<::domain_keys::models::ModelError> = #errors;
+            #custom
+
+            errors
+        }
+    })
+}
+
+fn expand_rule(field: &Field, rule: &Rule, span: proc_macro2::Span) -> syn::Result<TokenStream2> {
+    let ident = &field.ident;
+    let path = &field.path;
+    let rules = quote!(::domain_keys::validation);
+
+    let check = match rule {
+        Rule::Required if field.shape == Shape::Vec => {
+            return Ok(quote! {
+                .check(#path, !value.#ident.is_empty(), "required", "is required")
+            });
+        }
+        Rule::Required if field.shape == Shape::Option => {
+            return Ok(quote!(.field(#path, &value.#ident, &[&#rules::Required])));
+        }
+        Rule::Required => quote!(#rules::Required),
+        Rule::Email => quote!(#rules::Email),
+        Rule::Phone => quote!(#rules::Phone),
+        // the regex was checked when parsed; the static compiles it once on first use
+        Rule::Pattern(pattern) => quote! {
+            *{
+                static PATTERN: #rules::LazyPattern =
+                    #rules::LazyPattern::new(#pattern, "has an invalid format");
+                &PATTERN
+            }
+        },
+        Rule::OneOf(choices) => quote!(#rules::OneOf(&[#(#choices),*])),
+        Rule::Length { min, max } => match (min, max) {
+            (Some(min), Some(max)) => quote!(#rules::Length::new(#min, #max)),
+            (Some(min), None) => quote!(#rules::Length::min(#min)),
+            (None, Some(max)) => quote!(#rules::Length::max(#max)),
+            (None, None) => unreachable!("length bounds are checked when parsed"),
+        },
+        Rule::Range { min, max } => {
+            let range = quote!(#rules::Range::new(#min, #max));
+            return Ok(match field.shape {
+                Shape::Option => quote!(.optional(#path, value.#ident.as_ref(), &[&#range])),
+                _ => quote!(.field(#path, &value.#ident, &[&#range])),
+            });
+        }
+    };
+
+    let as_str = quote!(::core::convert::AsRef::<str>::as_ref);
+    Ok(match (field.shape, rule) {
+        (Shape::Vec, Rule::Length { .. }) => quote!(.field(#path, &value.#ident[..], &[&#check])),
+        (Shape::Vec, _) => {
+            return Err(syn::Error::new(
+                span,
+                "only required and length apply to Vec fields",
+            ))
+        }
+        (Shape::Option, _) => {
+            quote!(.optional(#path, value.#ident.as_ref().map(#as_str), &[&#check]))
+        }
+        (Shape::Plain, _) => quote!(.field(#path, #as_str(&value.#ident), &[&#check])),
+    })
+}
+
+fn expand_views(fields: &[Field]) -> Option<TokenStream2> {
+    let mut names: Vec<&str> = Vec::new();
+    for field in fields {
+        for view in &field.views {
+            if !names.contains(&view.as_str()) {
+                names.push(view);
+            }
+        }
+    }
+
+    if names.is_empty() && !fields.iter().any(|field| field.sensitive) {
+        return None;
+    }
+
+    let projection = quote!(::domain_keys::projection);
+    let views = names.iter().map(|name| {
+        let included: Vec<&Field> = fields
+            .iter()
+            .filter(|field| field.views.iter().any(|view| view == name))
+            .collect();
+        let include = included.iter().map(|field| format!("/value{}", field.path));
+        let redacted = if *name == "admin" {
+            None
+        } else {
+            Some(quote!(.redacted()))
+        };
+
+        quote! {
+            .view(
+                #projection::View::new(#name)
+                    .include(&["/key", "/status", #(#include),*])
+                    #redacted
+            )
+        }
+    });
+
+    let admin = if names.contains(&"admin") {
+        None
+    } else {
+        Some(quote!(.view(#projection::View::new(#projection::ADMIN))))
+    };
+
+    // a sensitive field that isn't a `Sensitive` fails to compile here, at the field's type
+    let checks: Vec<TokenStream2> = fields
+        .iter()
+        .filter(|field| field.sensitive)
+        .map(|field| {
+            let span = field.ty_span;
+            let ident = &field.ident;
+            let masked = quote_spanned!(span=> ::domain_keys::redact::Sensitive::masked);
+            match field.shape {
+                Shape::Plain => quote_spanned!(span=> let _ = #masked(&value.#ident);),
+                Shape::Option => quote_spanned!(span=> let _ = value.#ident.as_ref().map(#masked);),
+                Shape::Vec => {
+                    quote_spanned!(span=> let _ = value.#ident.iter().map(#masked).count();)
+                }
+            }
+        })
+        .collect();
+    let sensitive = if checks.is_empty() {
+        None
+    } else {
+        Some(quote!(let _ = |value: &Self| { #(#checks)* };))
+    };
+
+    Some(quote! {
+        fn views() -> #projection::Projection {
+            #sensitive
+
+            #projection::Projection::new()
+                #(#views)*
+                #admin
+        }
+    })
+}
@@ -0,0 +1,204 @@
+use domain_keys::keys::{KeyDomain, TimeStampId};
+use domain_keys::models::Transitions;
+use domain_keys::models::{DomainModel, Model, ModelError, Status, StatusFlags, StatusKind};
+use domain_keys::projection::{ADMIN, LIST, PUBLIC};
+use domain_keys::redact::{SensitiveEmail, SensitivePhone};
+use domain_keys::validation::ValidationReport;
+use serde_derive::{Deserialize, Serialize};
+
+#[derive(
+    Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, DomainModel,
+)]
+#[domain(
+    prefix = "usr",
+    transitions = "user_transitions",
+    validate = "adults_only"
+)]
+struct User {
+    #[validate(required, length(min = 2, max = 40))]
+    #[view(list, public)]
+    name: String,
+    #[validate(required, email)]
+    #[view(list)]
+    #[sensitive]
+    email: SensitiveEmail<String>,
+    #[validate(phone)]
+    #[view(list)]
+    phone: SensitivePhone<String>,
+    #[validate(range(min = 0, max = 150))]
+    age: u8,
+    #[validate(one_of("admin", "member"))]
+    #[serde(rename = "user-role")]
+    #[view(public)]
+    role: String,
+    #[validate(pattern = "^[0-9]{5}$")]
+    zip: Option<String>,
+    #[validate(required, length(max = 2))]
+    tags: Vec<String>,
+}
+
+fn user_transitions() -> Transitions {
+    Transitions::default().deny(StatusKind::New, StatusKind::Pending)
+}
+
+fn adults_only(model: &Model<User, <User as DomainModel>::Key>) -> Vec<ModelError> {
+    let mut errors = Vec::new();
+    if model.value.age < 18 {
+        errors.push(ModelError::Email("must be an adult".into()));
+    }
+
+    errors
+}
+
+fn user() -> User {
+    User {
+        name: "sam".to_string(),
+        email: "sam@rcs.com".to_string().into(),
+        phone: "123-555-3333".to_string().into(),
+        age: 42,
+        role: "member".to_string(),
+        zip: Some("98101".to_string()),
+        tags: vec!["a".to_string()],
+    }
+}
+
+#[derive(
+    Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, DomainModel,
+)]
+#[domain(key = "TimeStampId", schema = 2)]
+struct Event {
+    name: String,
+}
+
+#[derive(
+    Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash, DomainModel,
+)]
+struct Note {
+    #[validate(required)]
+    text: Option<String>,
+}
+
+#[test]
+fn valid_model() {
+    let model = Model::new_domain(user());
+    assert!(User::validate(&model).is_empty());
+}
+
+#[test]
+fn field_rules() {
+    let mut value = user();
+    value.name = "s".to_string();
+    value.email = String::new().into();
+    value.phone = "12".to_string().into();
+    value.age = 200;
+    value.role = "owner".to_string();
+    value.zip = Some("abc".to_string());
+    value.tags = Vec::new();
+
+    let report = ValidationReport::from(User::validate(&Model::new_domain(value)));
+    let errors: Vec<(&str, &str)> = report
+        .errors
+        .iter()
+        .map(|e| (e.path.as_str(), e.code.as_str()))
+        .collect();
+
+    assert_eq!(
+        errors,
+        vec![
+            ("/name", "length"),
+            ("/email", "required"),
+            ("/phone", "phone"),
+            ("/age", "range"),
+            ("/user-role", "one_of"),
+            ("/zip", "pattern"),
+            ("/tags", "required"),
+        ]
+    );
+
+    // an empty option isn't checked and the custom validation runs last
+    let mut value = user();
+    value.zip = None;
+    value.age = 12;
+    value.tags = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+
+    let errors = User::validate(&Model::new_domain(value));
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].to_string(), "/tags: length must be at most 2");
+    assert_eq!(errors[1], ModelError::Email("must be an adult".into()));
+}
+
+#[test]
+fn required_option() {
+    let note: Model<Note> = Model::new(Note { text: None });
+    let errors = Note::validate(&note);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].to_string(), "/text: is required");
+
+    let note: Model<Note> = Model::new(Note {
+        text: Some(String::new()),
+    });
+    assert!(Note::validate(&note).is_empty());
+}
+
+#[test]
+fn views() {
+    assert_eq!(User::views().names(), vec![LIST, PUBLIC, ADMIN]);
+
+    let model = Model::new_domain(user());
+
+    let list = model.view(LIST).unwrap();
+    assert_eq!(list["key"], model.key.to_string());
+    assert_eq!(list["status"]["t"], "New");
+    assert_eq!(list["value"]["name"], "sam");
+    assert_eq!(list["value"]["email"], "s***@rcs.com");
+    assert_eq!(list["value"]["phone"], "***-***-3333");
+    assert!(list["value"].get("age").is_none());
+    assert!(list.get("version").is_none());
+
+    let public = model.view(PUBLIC).unwrap();
+    assert_eq!(public["value"]["user-role"], "member");
+    assert!(public["value"].get("email").is_none());
+
+    let admin = model.view(ADMIN).unwrap();
+    assert_eq!(admin["value"]["email"], "sam@rcs.com");
+    assert_eq!(admin["value"]["phone"], "123-555-3333");
+
+    let debug = format!("{:?}", model.value);
+    assert!(debug.contains(r#"email: "s***@rcs.com""#));
+    assert!(!debug.contains("sam@rcs.com"));
+    assert_eq!(admin["value"]["age"], 42);
+
+    assert!(Note::views().names().is_empty());
+}
+
+#[test]
+fn key_domains() {
+    assert_eq!(User::PREFIX, "usr");
+
+    let model = Model::new_domain(user());
+    assert!(model.key.starts_with("usr_"));
+
+    let json = serde_json::to_string(&model).unwrap();
+    let round_trip: Model<User, <User as DomainModel>::Key> = serde_json::from_str(&json).unwrap();
+    assert_eq!(round_trip, model);
+
+    // a routing key isn't a user key
+    let json = serde_json::to_string(&Model::new(user())).unwrap();
+    assert!(serde_json::from_str::<Model<User, <User as DomainModel>::Key>>(&json).is_err());
+
+    let event: Model<Event, TimeStampId> = Model::new_domain(Event::default());
+    assert_eq!(event.key.len(), 12);
+    assert_eq!(event.schema, 2);
+    assert_eq!(model.schema, 1);
+    assert!(Event::validate(&event).is_empty());
+}
+
+#[test]
+fn transitions() {
+    let model = Model::new_domain(user());
+
+    assert!(model
+        .transition(Status::Pending(StatusFlags::NONE))
+        .is_err());
+    assert!(model.transition(Status::Active(StatusFlags::NONE)).is_ok());
+}
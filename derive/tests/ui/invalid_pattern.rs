@@ -0,0 +1,9 @@
+use domain_keys::models::DomainModel;
+
+#[derive(Debug, Clone, serde_derive::Serialize, DomainModel)]
+struct Item {
+    #[validate(pattern = "^[0-9]{5$")]
+    zip: String,
+}
+
+fn main() {}
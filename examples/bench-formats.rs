@@ -0,0 +1,63 @@
+use domain_keys::models::Model;
+use domain_keys::tenant::TenantId;
+use domain_keys::wire::{self, Format};
+use serde_derive::{Deserialize, Serialize};
+use std::time::Instant;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Person {
+    first_name: String,
+    last_name: String,
+    email: String,
+    age: u8,
+    score: f64,
+    tags: Vec<String>,
+}
+
+fn create_model(id: usize) -> Model<Person> {
+    let person = Person {
+        first_name: format!("first-{}", id),
+        last_name: format!("last-{}", id),
+        email: format!("user{}@rcs.com", id),
+        age: (id % 100) as u8,
+        score: id as f64 / 7.0,
+        tags: vec!["member".to_string(), "beta".to_string()],
+    };
+
+    Model::new(person).with_tenant(TenantId::new("acme").unwrap())
+}
+
+// run with --release --all-features to include the binary formats
+fn main() {
+    let total_models = 100_000_usize;
+    println!("Total Models: {}", total_models);
+
+    let models: Vec<Model<Person>> = (0..total_models).map(create_model).collect();
+
+    for format in Format::ALL.iter().filter(|format| format.is_enabled()) {
+        let now = Instant::now();
+        let encoded: Vec<Vec<u8>> = models
+            .iter()
+            .map(|model| wire::encode(model, *format).unwrap())
+            .collect();
+        let encode_elapsed = now.elapsed();
+
+        let now = Instant::now();
+        for bytes in encoded.iter() {
+            let (_, model): (_, Model<Person>) = wire::decode(bytes).unwrap();
+            assert_eq!(model.value.tags.len(), 2);
+        }
+        let decode_elapsed = now.elapsed();
+
+        let total_bytes: usize = encoded.iter().map(|bytes| bytes.len()).sum();
+
+        println!(
+            "{:>8}: {} bytes, {} bytes per model, encode {} ms, decode {} ms",
+            format.to_string(),
+            total_bytes,
+            total_bytes / total_models,
+            encode_elapsed.as_millis(),
+            decode_elapsed.as_millis()
+        );
+    }
+}
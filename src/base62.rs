@@ -3,20 +3,75 @@
 //! The library and associated binary encode u64 to String and decode &str to Result<u64, Error>.
 //! The character set is `[0..9A..Za..z]`.
 //!
+//! Base62 uses the [`crate::codec::BASE62`] codec; see the codec module for alternate alphabets.
+//!
 
-// base62 conversion table
-const ALPHA: [char; 62] = [
-    '0', '1', '2', '3', '4', '5', '6', '7', '8', '9', 'A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I',
-    'J', 'K', 'L', 'M', 'N', 'O', 'P', 'Q', 'R', 'S', 'T', 'U', 'V', 'W', 'X', 'Y', 'Z', 'a', 'b',
-    'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u',
-    'v', 'w', 'x', 'y', 'z',
-];
-
-/// Empty string and invalid chars; used in decode.
-#[derive(Debug)]
+use crate::codec::BASE62;
+use crate::keys::DomainKeyError;
+use std::fmt;
+
+/// Empty string, invalid chars and u64 overflow; used in decode.  InvalidChar carries the
+/// offending char and its zero based char index.  Key wraps a key error that isn't a base62
+/// error, so the two error types convert into each other with `From`.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::base62::{Base62, Base62Error};
+///
+/// let err = Base62::decode("AB~CY").unwrap_err();
+///
+/// assert_eq!(err, Base62Error::InvalidChar { ch: '~', index: 2 });
+/// assert_eq!(err.to_string(), "invalid char '~' at index 2");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Base62Error {
     EmptyString,
-    InvalidChar,
+    InvalidChar {
+        ch: char,
+        index: usize,
+    },
+    Overflow,
+    /// a key error other than `DomainKeyError::InvalidBase62`
+    Key(Box<DomainKeyError>),
+}
+
+impl Base62Error {
+    /// Shift the char index by offset, e.g., when decoding a slice of a larger key.
+    pub fn offset(self, offset: usize) -> Base62Error {
+        match self {
+            Base62Error::InvalidChar { ch, index } => Base62Error::InvalidChar {
+                ch,
+                index: index + offset,
+            },
+            other => other,
+        }
+    }
+}
+
+impl fmt::Display for Base62Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Base62Error::EmptyString => write!(f, "empty string"),
+            Base62Error::InvalidChar { ch, index } => {
+                write!(f, "invalid char {:?} at index {}", ch, index)
+            }
+            Base62Error::Overflow => write!(f, "value is too large for a u64"),
+            Base62Error::Key(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for Base62Error {}
+
+impl From<DomainKeyError> for Base62Error {
+    /// Unwrap the base62 error from a key error; other key errors are wrapped in `Key`.
+    fn from(err: DomainKeyError) -> Self {
+        match err {
+            DomainKeyError::InvalidBase62(err) => err,
+            other => Base62Error::Key(Box::new(other)),
+        }
+    }
 }
 
 /// The magic starts here... an empty struct.
@@ -61,22 +116,7 @@ impl Base62 {
     /// ```
     ///
     pub fn encode(number: u64) -> String {
-        let radix = ALPHA.len() as u64;
-        let mut n = number;
-        let mut base: Vec<char> = Vec::with_capacity(16);
-
-        loop {
-            let idx = (n % radix) as usize;
-            base.push(ALPHA[idx]);
-
-            n /= radix;
-
-            if n == 0 {
-                break;
-            }
-        }
-
-        base.iter().rev().collect::<String>()
+        BASE62.encode(number)
     }
 
     ///
@@ -98,41 +138,7 @@ impl Base62 {
     ///
     /// ```
     pub fn decode(b62: &str) -> Result<u64, Base62Error> {
-        // validate string is not empty
-        if b62.is_empty() {
-            return Err(Base62Error::EmptyString);
-        }
-
-        let radix = 62_u64;
-        let mut result = 0_u64;
-
-        for (p, ch) in b62.chars().rev().enumerate() {
-            let n = match Self::decode_digit(ch as u8) {
-                Ok(n) => n as u64,
-                Err(err) => return Err(err),
-            };
-            let q = radix.pow(p as u32);
-
-            result += n * q;
-        }
-
-        Ok(result)
-    }
-
-    fn decode_digit(digit: u8) -> Result<u8, Base62Error> {
-        const ZERO: u8 = 48;
-        const NINE: u8 = 57;
-        const BIG_A: u8 = 65;
-        const BIG_Z: u8 = 65 + 26;
-        const LITTLE_A: u8 = 97;
-        const LITTLE_Z: u8 = 97 + 26;
-
-        match digit {
-            ZERO..=NINE => Ok(digit - ZERO),
-            BIG_A..=BIG_Z => Ok(digit - BIG_A + 10),
-            LITTLE_A..=LITTLE_Z => Ok(digit - LITTLE_A + 36),
-            _ => Err(Base62Error::InvalidChar),
-        }
+        BASE62.decode(b62)
     }
 }
 
@@ -162,7 +168,7 @@ mod tests {
             Err(err) => println!("err: {:?}", err),
         }
 
-        assert!(Base62::decode(&"".to_string()).is_err());
+        assert!(Base62::decode("").is_err());
     }
 
     #[test]
@@ -173,8 +179,8 @@ mod tests {
             Err(err) => println!("err: {:?}", err),
         }
 
-        assert!(Base62::decode(&"LLLL&GG".to_string()).is_err());
-        assert!(Base62::decode(&"-bad".to_string()).is_err());
+        assert!(Base62::decode("LLLL&GG").is_err());
+        assert!(Base62::decode("-bad").is_err());
     }
 
     #[test]
@@ -200,19 +206,19 @@ mod tests {
         let mut n = 0_u8;
 
         for x in b'0'..=b'9' {
-            let y = Base62::decode_digit(x).unwrap();
+            let y = BASE62.decode_digit(x as char, 0).unwrap().unwrap();
             assert_eq!(y, n);
             n += 1;
         }
 
         for x in b'A'..=b'Z' {
-            let y = Base62::decode_digit(x).unwrap();
+            let y = BASE62.decode_digit(x as char, 0).unwrap().unwrap();
             assert_eq!(y, n);
             n += 1;
         }
 
         for x in b'a'..=b'z' {
-            let y = Base62::decode_digit(x).unwrap();
+            let y = BASE62.decode_digit(x as char, 0).unwrap().unwrap();
             assert_eq!(y, n);
             n += 1;
         }
@@ -220,9 +226,8 @@ mod tests {
         assert_eq!(n, 62);
 
         n = 0;
-        for ch in ALPHA {
-            let x = ch as u8;
-            let y = Base62::decode_digit(x).unwrap();
+        for ch in BASE62.alphabet() {
+            let y = BASE62.decode_digit(*ch as char, 0).unwrap().unwrap();
             assert_eq!(y, n);
 
             n += 1;
@@ -243,7 +248,7 @@ mod tests {
         ];
 
         for (b62, n) in list {
-            let value = Base62::decode(&b62.to_string()).unwrap();
+            let value = Base62::decode(b62).unwrap();
             assert_eq!(n, value);
         }
     }
@@ -2,7 +2,7 @@
 //! Base62 encode and decode
 //!
 //! A command line utility to encode a u64 number to a base62 string with characters [0..9A..Za..z] or
-//! to decode a base62 string to a u64 number.
+//! to decode a base62 string to a u64 number.  `--alphabet` selects another codec, e.g., base36.
 //!
 //! # Examples:
 //!
@@ -15,11 +15,30 @@
 //!
 //! base62 -t
 //! 1665071772589928000 -> 1z02sIVcGSu
+//!
+//! base62 -d 'AB~CY'
+//! Error parsing base62 string: invalid char '~' at index 2
+//!
+//! base62 -a base36 -e 12345
+//! 9ix
+//!
+//! # decode the second csv column of every line from stdin; bad values are left empty and reported to stderr
+//! cat keys.csv | base62 --stream decode --column 2 --header
 //! ```
 //!
+//! Exits with 0 on success, 64 for usage errors and 65 for invalid input; see `src/bin/common`.
+//!
+
+mod common;
 
 use clap::Parser;
-use domain_keys::base62::Base62;
+use common::Exit;
+use domain_keys::codec::Codec;
+use domain_keys::stream::{convert_stream, Direction, StreamConfig, StreamSummary};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::PathBuf;
+use std::process::ExitCode;
 
 #[derive(Debug, Default, Parser)]
 #[clap(name = "base62")]
@@ -30,7 +49,7 @@ use domain_keys::base62::Base62;
 pub struct CliArgs {
     /// encodes a u64 and outputs the string. `base62 -e 12345` -> 3d7
     #[clap(short, long, value_parser, value_name = "u64_number")]
-    pub encode: Option<u64>,
+    pub encode: Option<String>,
 
     /// decodes a base62 encoded string [0..9A..Za..z] and outputs the integer.  `base62 -d 3d7` -> 12345
     #[clap(short, long, value_parser, value_name = "base62_string")]
@@ -40,52 +59,165 @@ pub struct CliArgs {
     #[clap(short, long, value_parser)]
     pub timestamp: bool,
 
-    /// show what was encoded/decoded
+    /// show what was encoded/decoded; in stream mode show the line counts
     #[clap(short, long, value_parser)]
     pub verbose: bool,
-}
 
-impl CliArgs {
-    pub fn new() -> Self {
-        Self::parse()
-    }
+    /// encode or decode each line read from stdin or the input files; can't be combined with
+    /// --encode, --decode or --timestamp
+    #[clap(
+        short,
+        long,
+        value_parser,
+        value_name = "encode|decode",
+        conflicts_with_all = ["encode", "decode", "timestamp"]
+    )]
+    pub stream: Option<Direction>,
+
+    /// the 1-based csv column to convert in stream mode; other columns pass through
+    #[clap(short, long, value_parser, value_name = "n")]
+    pub column: Option<usize>,
+
+    /// the alphabet: base62, base62-inverted, base58, base36 or crockford32
+    #[clap(short, long, value_parser = common::parse_codec, default_value = "base62")]
+    pub alphabet: Codec,
+
+    /// the csv column delimiter for stream mode
+    #[clap(long, value_parser, default_value = ",")]
+    pub delimiter: char,
+
+    /// pass the first line through unchanged in stream mode
+    #[clap(long, value_parser)]
+    pub header: bool,
+
+    /// input files for stream mode; reads stdin when none are given or the file is `-`
+    #[clap(value_parser, value_name = "file")]
+    pub files: Vec<PathBuf>,
 }
 
-fn show_decode(s: &str, verbose: bool) {
-    match Base62::decode(s) {
+fn show_decode(codec: &Codec, s: &str, verbose: bool) -> Exit {
+    match codec.decode(s) {
         Ok(n) => {
             if verbose {
                 println!("{} -> {}", s, n);
             } else {
                 println!("{}", n);
             }
+            Exit::Ok
+        }
+        Err(err) => {
+            eprintln!("Error parsing {} string: {}", codec.name(), err);
+            Exit::Data
         }
-        Err(err) => eprintln!("Error parsing Base62 string: {:?}", err),
     }
 }
 
-fn show_encode(n: u64, verbose: bool) {
-    let value = Base62::encode(n);
+// parsed here rather than by clap so a bad number is a data error, not a usage error
+fn show_encode(codec: &Codec, s: &str, verbose: bool) -> Exit {
+    let n = match s.trim().parse::<u64>() {
+        Ok(n) => n,
+        Err(e) => {
+            eprintln!("Error parsing u64 number: {} {}", s, e);
+            return Exit::Data;
+        }
+    };
+
+    let value = codec.encode(n);
     if verbose {
         println!("{} -> {}", n, value);
     } else {
         println!("{}", value);
     }
+
+    Exit::Ok
 }
 
-fn main() {
-    let args = CliArgs::new();
+fn show_stream(args: &CliArgs, direction: Direction) -> Result<StreamSummary, Exit> {
+    let mut config = StreamConfig::new(direction);
+    config.codec = args.alphabet;
+    config.column = args.column;
+    config.delimiter = args.delimiter;
+    config.header = args.header;
 
-    // println!("{:?}", args);
+    let stdout = io::stdout();
+    let stderr = io::stderr();
+    let mut out = BufWriter::new(stdout.lock());
+    let mut err = stderr.lock();
 
-    if let Some(n) = args.encode {
-        show_encode(n, args.verbose);
-    } else if let Some(base62) = args.decode {
-        show_decode(&base62, args.verbose)
+    let stdin_path = PathBuf::from("-");
+    let files = if args.files.is_empty() {
+        std::slice::from_ref(&stdin_path)
+    } else {
+        &args.files[..]
+    };
+
+    let mut summary = StreamSummary::default();
+    for path in files {
+        let result = if path == &stdin_path {
+            let stdin = io::stdin();
+            convert_stream(&config, "stdin", stdin.lock(), &mut out, &mut err)
+        } else {
+            let source = path.display().to_string();
+            let file = match File::open(path) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error opening {}: {}", source, e);
+                    return Err(Exit::NoInput);
+                }
+            };
+            convert_stream(&config, &source, BufReader::new(file), &mut out, &mut err)
+        };
+
+        match result {
+            Ok(file_summary) => summary.add(file_summary),
+            Err(e) => {
+                eprintln!("Error reading stream: {}", e);
+                return Err(Exit::Io);
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+fn main() -> ExitCode {
+    let args: CliArgs = match common::parse_args() {
+        Ok(args) => args,
+        Err(exit) => return exit.into(),
+    };
+
+    let exit = if let Some(n) = &args.encode {
+        show_encode(&args.alphabet, n, args.verbose)
+    } else if let Some(encoded) = &args.decode {
+        show_decode(&args.alphabet, encoded, args.verbose)
+    } else if let Some(direction) = args.stream {
+        match show_stream(&args, direction) {
+            Ok(summary) => {
+                if args.verbose {
+                    eprintln!(
+                        "lines: {}, converted: {}, errors: {}",
+                        summary.lines, summary.converted, summary.errors
+                    );
+                }
+
+                if summary.errors > 0 {
+                    Exit::Data
+                } else {
+                    Exit::Ok
+                }
+            }
+            Err(exit) => exit,
+        }
     } else if args.timestamp {
         let now = domain_keys::keys::Keys::now() as u64;
-        println!("{} -> {}", now, Base62::encode(now));
+        println!("{} -> {}", now, args.alphabet.encode(now));
+        Exit::Ok
     } else {
-        println!("\nError: must add switch to --encode or --decode; try base62 --help\n");
-    }
+        eprintln!(
+            "\nError: must add switch to --encode, --decode or --stream; try base62 --help\n"
+        );
+        Exit::Usage
+    };
+
+    exit.into()
 }
@@ -0,0 +1,109 @@
+//!
+//! Shared helpers for the command line binaries.  Exit codes follow the BSD `sysexits` values so
+//! scripts can tell a usage error from invalid input.
+//!
+//! Each binary includes this module with `mod common;` so the helpers stay out of the library's
+//! public api; a binary that doesn't use every helper would otherwise warn about dead code.
+//!
+#![allow(dead_code)]
+
+use clap::Parser;
+use domain_keys::codec::Codec;
+use std::process::ExitCode;
+
+/// Process exit codes used by the binaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exit {
+    /// success
+    Ok = 0,
+    /// bad command line arguments or switches
+    Usage = 64,
+    /// invalid input, e.g., a bad base62 string or routing key
+    Data = 65,
+    /// an input file could not be opened
+    NoInput = 66,
+    /// an internal error
+    Software = 70,
+    /// an error reading input or writing output
+    Io = 74,
+}
+
+impl Exit {
+    /// Return the numeric exit code.
+    pub fn code(self) -> u8 {
+        self as u8
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit.code())
+    }
+}
+
+/// Parse the command line args.  Help and version are written to stdout and return `Exit::Ok`;
+/// any other parse failure is written to stderr and returns `Exit::Usage`.
+pub fn parse_args<T: Parser>() -> Result<T, Exit> {
+    match T::try_parse() {
+        Ok(args) => Ok(args),
+        Err(err) => {
+            let _ = err.print();
+            if err.use_stderr() {
+                Err(Exit::Usage)
+            } else {
+                Err(Exit::Ok)
+            }
+        }
+    }
+}
+
+/// Parse an `--alphabet` name to its codec, e.g., as a clap `value_parser`; names are those of
+/// `Codec::from_name`.
+pub fn parse_codec(name: &str) -> Result<Codec, String> {
+    Codec::from_name(name).ok_or_else(|| format!("unknown alphabet: {}", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(Exit::Ok.code(), 0);
+        assert_eq!(Exit::Usage.code(), 64);
+        assert_eq!(Exit::Data.code(), 65);
+    }
+
+    #[test]
+    fn distinct_codes() {
+        let codes = [
+            Exit::Ok,
+            Exit::Usage,
+            Exit::Data,
+            Exit::NoInput,
+            Exit::Software,
+            Exit::Io,
+        ];
+
+        for (i, a) in codes.iter().enumerate() {
+            for b in &codes[i + 1..] {
+                assert_ne!(a.code(), b.code());
+            }
+        }
+    }
+
+    #[test]
+    fn codec_names() {
+        for name in [
+            "base62",
+            "base62-inverted",
+            "base58",
+            "base36",
+            "crockford32",
+        ] {
+            assert_eq!(parse_codec(name).unwrap().name(), name);
+        }
+
+        assert_eq!(parse_codec("hex"), Err("unknown alphabet: hex".to_string()));
+    }
+}
@@ -11,8 +11,13 @@
 //! iGqc7coW37bHH8AY
 //! ```
 //!
+mod common;
+
 use clap::Parser;
+use common::Exit;
+use domain_keys::codec::Codec;
 use domain_keys::keys::RouteKey;
+use std::process::ExitCode;
 
 #[derive(Debug, Default, Parser)]
 #[clap(name = "rtkey")]
@@ -29,33 +34,37 @@ pub struct CliArgs {
 
     #[clap(short, long, value_parser, default_value = "1")]
     pub count: u16,
-}
 
-impl CliArgs {
-    pub fn new() -> Self {
-        Self::parse()
-    }
+    /// the key alphabet: base62, base62-inverted, base58, base36 or crockford32
+    #[clap(short, long, value_parser = common::parse_codec, default_value = "base62")]
+    pub alphabet: Codec,
 }
 
-fn main() {
-    // let config = Config::new();
-    let args = CliArgs::new();
+fn main() -> ExitCode {
+    let args: CliArgs = match common::parse_args() {
+        Ok(args) => args,
+        Err(exit) => return exit.into(),
+    };
 
-    let key = RouteKey::create();
+    let codec = args.alphabet;
+    let key = RouteKey::create_with(&codec);
 
-    assert_eq!(key.len(), 16);
+    assert_eq!(key.len(), RouteKey::key_size(&codec));
 
     if args.count > 1 {
         print!("{} ", key);
         for _ in 1..args.count {
-            print!("{} ", RouteKey::create());
+            print!("{} ", RouteKey::create_with(&codec));
         }
         println!();
     } else if !args.verbose {
         println!("{}", key);
-    } else if let Ok(ts) = RouteKey::parse_timestamp(&key) {
+    } else if let Ok(ts) = RouteKey::parse_timestamp_with(&key, &codec) {
         println!("Key: {}, TimeStamp: {}", key, ts);
     } else {
         println!("Key: {}, TimeStamp: ERROR", key);
+        return Exit::Software.into();
     }
+
+    Exit::Ok.into()
 }
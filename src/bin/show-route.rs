@@ -1,3 +1,57 @@
-fn main() {
-    println!("implement me!");
+//!
+//! show-route - show the route number (0..n) for a routing key.
+//!
+//! # Examples:
+//!
+//! ```bash
+//! show-route --routes 25 YM6I7clU96YvDTCr
+//! 5
+//! ```
+//!
+//! Exits with 65 when the key is not a valid routing key.
+//!
+mod common;
+
+use clap::Parser;
+use common::Exit;
+use domain_keys::codec::Codec;
+use domain_keys::keys::RouteKey;
+use std::process::ExitCode;
+
+#[derive(Debug, Default, Parser)]
+#[clap(name = "show-route")]
+#[command(author)]
+#[clap(version = "0.7.1")]
+#[clap(long_about = None)]
+#[clap(about = "show-route\n\nshow the route number (0..n) for the routing key.")]
+pub struct CliArgs {
+    /// the total number of routes, clamped to 1..=128
+    #[clap(short, long, value_parser, default_value = "1")]
+    pub routes: u8,
+
+    /// the key alphabet: base62, base62-inverted, base58, base36 or crockford32
+    #[clap(short, long, value_parser = common::parse_codec, default_value = "base62")]
+    pub alphabet: Codec,
+
+    /// the routing key
+    #[clap(value_parser)]
+    pub key: String,
+}
+
+fn main() -> ExitCode {
+    let args: CliArgs = match common::parse_args() {
+        Ok(args) => args,
+        Err(exit) => return exit.into(),
+    };
+
+    match RouteKey::parse_route_with(&args.key, args.routes, &args.alphabet) {
+        Ok(route) => {
+            println!("{}", route);
+            Exit::Ok.into()
+        }
+        Err(err) => {
+            eprintln!("Error parsing route for key {}: {}", args.key, err);
+            Exit::Data.into()
+        }
+    }
 }
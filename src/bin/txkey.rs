@@ -12,8 +12,13 @@
 //! 7coWEn0Efg2p
 //! ```
 //!
+mod common;
+
 use clap::Parser;
+use common::Exit;
+use domain_keys::codec::Codec;
 use domain_keys::keys::TimeStampKey;
+use std::process::ExitCode;
 
 #[derive(Debug, Default, Parser)]
 #[clap(name = "txkey")]
@@ -25,18 +30,28 @@ pub struct CliArgs {
     /// set verbose to show the timestamp with the key
     #[clap(short, long, value_parser)]
     pub verbose: bool,
+
+    /// the key alphabet: base62, base62-inverted, base58, base36 or crockford32
+    #[clap(short, long, value_parser = common::parse_codec, default_value = "base62")]
+    pub alphabet: Codec,
 }
 
-fn main() {
-    let args = CliArgs::parse();
+fn main() -> ExitCode {
+    let args: CliArgs = match common::parse_args() {
+        Ok(args) => args,
+        Err(exit) => return exit.into(),
+    };
 
-    let key = TimeStampKey::create();
+    let key = TimeStampKey::create_with(&args.alphabet);
 
     if !args.verbose {
         println!("{}", key);
-    } else if let Ok(ts) = TimeStampKey::parse_timestamp(&key) {
+    } else if let Ok(ts) = TimeStampKey::parse_timestamp_with(&key, &args.alphabet) {
         println!("Key: {}, TimeStamp: {}", key, ts);
     } else {
         println!("Key: {}, TimeStamp: ERROR", key);
+        return Exit::Software.into();
     }
+
+    Exit::Ok.into()
 }
@@ -0,0 +1,474 @@
+//!
+//! Alphabet-parametric codec used by base62 and the key generators.  Built-in codecs include
+//! Base62 `[0-9A-Za-z]`, Base58 (bitcoin), Base36 `[0-9a-z]` and
+//! [Crockford Base32](https://www.crockford.com/base32.html).
+//!
+//! Base36 and Crockford Base32 decode case-insensitively, so they are safe for case-insensitive
+//! database collations, DNS labels, or reading a key over the phone.
+//!
+
+use crate::base62::Base62Error;
+
+// lookup table markers; any value < 64 is the digit value
+const INVALID: u8 = 0xff;
+const SKIP: u8 = 0xfe;
+
+/// Base62 `[0-9A-Za-z]`; the crate default.
+pub const BASE62: Codec = Codec::new(
+    "base62",
+    b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz",
+);
+
+/// Base62 with the inverted `[0-9a-zA-Z]` ordering used by many python and javascript libraries.
+pub const BASE62_INVERTED: Codec = Codec::new(
+    "base62-inverted",
+    b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ",
+);
+
+/// Base58 with the bitcoin alphabet; drops `0`, `O`, `I` and `l`.
+pub const BASE58: Codec = Codec::new(
+    "base58",
+    b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz",
+);
+
+/// Base36 `[0-9a-z]`; encodes to lower case and decodes either case.
+pub const BASE36: Codec =
+    Codec::new("base36", b"0123456789abcdefghijklmnopqrstuvwxyz").ignore_case();
+
+/// Crockford Base32; encodes to upper case, decodes either case, reads `I`/`L` as `1`, `O` as `0`
+/// and ignores hyphens.
+pub const CROCKFORD32: Codec = Codec::new("crockford32", b"0123456789ABCDEFGHJKMNPQRSTVWXYZ")
+    .ignore_case()
+    .alias(b'I', b'1')
+    .alias(b'L', b'1')
+    .alias(b'O', b'0')
+    .skip(b'-');
+
+/// The built-in codecs.
+pub const CODECS: [Codec; 5] = [BASE62, BASE62_INVERTED, BASE58, BASE36, CROCKFORD32];
+
+/// An encoder/decoder for u64 values over a fixed alphabet.  Codecs are const constructed so the
+/// decode lookup table is built at compile time.
+#[derive(Debug, Clone, Copy)]
+pub struct Codec {
+    name: &'static str,
+    alphabet: &'static [u8],
+    lookup: [u8; 256],
+}
+
+impl Codec {
+    /// Create a new codec from a unique set of 2..=64 ascii characters.  The position of each
+    /// character is its digit value.  Panics (at compile time when used in a const) if the
+    /// alphabet is invalid.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::Codec;
+    ///
+    /// const HEX: Codec = Codec::new("hex", b"0123456789abcdef").ignore_case();
+    ///
+    /// assert_eq!(HEX.encode(255), "ff");
+    /// assert_eq!(HEX.decode("FF").unwrap(), 255);
+    /// ```
+    pub const fn new(name: &'static str, alphabet: &'static [u8]) -> Codec {
+        assert!(
+            alphabet.len() >= 2 && alphabet.len() <= 64,
+            "alphabet size must be 2..=64"
+        );
+
+        let mut lookup = [INVALID; 256];
+        let mut idx = 0;
+        while idx < alphabet.len() {
+            let ch = alphabet[idx];
+            assert!(ch.is_ascii_graphic(), "alphabet must be printable ascii");
+            assert!(
+                lookup[ch as usize] == INVALID,
+                "alphabet has duplicate chars"
+            );
+            lookup[ch as usize] = idx as u8;
+            idx += 1;
+        }
+
+        Codec {
+            name,
+            alphabet,
+            lookup,
+        }
+    }
+
+    /// Accept both upper and lower case letters when decoding.
+    pub const fn ignore_case(self) -> Codec {
+        let mut lookup = self.lookup;
+        let mut idx = 0;
+        while idx < self.alphabet.len() {
+            let ch = self.alphabet[idx];
+            let other = if ch.is_ascii_uppercase() {
+                ch.to_ascii_lowercase()
+            } else {
+                ch.to_ascii_uppercase()
+            };
+
+            if lookup[other as usize] == INVALID {
+                lookup[other as usize] = idx as u8;
+            }
+            idx += 1;
+        }
+
+        Codec { lookup, ..self }
+    }
+
+    /// Decode the `from` char (and its other case) as the digit `to`.
+    pub const fn alias(self, from: u8, to: u8) -> Codec {
+        let mut lookup = self.lookup;
+        let value = lookup[to as usize];
+        assert!(value < SKIP, "alias target must be in the alphabet");
+
+        lookup[from.to_ascii_uppercase() as usize] = value;
+        lookup[from.to_ascii_lowercase() as usize] = value;
+
+        Codec { lookup, ..self }
+    }
+
+    /// Silently skip the char when decoding, e.g., a separator.
+    pub const fn skip(self, ch: u8) -> Codec {
+        let mut lookup = self.lookup;
+        lookup[ch as usize] = SKIP;
+
+        Codec { lookup, ..self }
+    }
+
+    /// Return the built-in codec for the name, e.g., `base36`; the match is case-insensitive.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::{Codec, CROCKFORD32};
+    ///
+    /// assert_eq!(Codec::from_name("Crockford32"), Some(CROCKFORD32));
+    /// assert!(Codec::from_name("base64").is_none());
+    /// ```
+    pub fn from_name(name: &str) -> Option<Codec> {
+        CODECS
+            .iter()
+            .find(|c| c.name.eq_ignore_ascii_case(name))
+            .copied()
+    }
+
+    /// The codec name, e.g., `base62`.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The encoding characters in digit order.
+    pub fn alphabet(&self) -> &'static [u8] {
+        self.alphabet
+    }
+
+    /// The number base for this codec.
+    pub fn radix(&self) -> u64 {
+        self.alphabet.len() as u64
+    }
+
+    /// The zero char used for padding.
+    pub fn zero(&self) -> char {
+        self.alphabet[0] as char
+    }
+
+    /// Encode the u64 number.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::{BASE36, BASE58, BASE62, CROCKFORD32};
+    ///
+    /// let n = 1_000_000_u64;
+    ///
+    /// assert_eq!(BASE62.encode(n), "4C92");
+    /// assert_eq!(BASE58.encode(n), "68GP");
+    /// assert_eq!(BASE36.encode(n), "lfls");
+    /// assert_eq!(CROCKFORD32.encode(n), "YGJ0");
+    /// ```
+    pub fn encode(&self, number: u64) -> String {
+        let radix = self.radix();
+        let mut n = number;
+        let mut base: Vec<u8> = Vec::with_capacity(16);
+
+        loop {
+            base.push(self.alphabet[(n % radix) as usize]);
+
+            n /= radix;
+
+            if n == 0 {
+                break;
+            }
+        }
+
+        base.iter().rev().map(|b| *b as char).collect::<String>()
+    }
+
+    /// Encode the u64 number and left pad with the zero char to the given width.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::{BASE36, BASE58};
+    ///
+    /// assert_eq!(BASE36.encode_with_pad(35, 4), "000z");
+    /// assert_eq!(BASE58.encode_with_pad(0, 3), "111");
+    /// ```
+    pub fn encode_with_pad(&self, number: u64, width: usize) -> String {
+        let encoded = self.encode(number);
+        if encoded.len() >= width {
+            return encoded;
+        }
+
+        let mut padded: String = std::iter::repeat(self.zero())
+            .take(width - encoded.len())
+            .collect();
+        padded.push_str(&encoded);
+
+        padded
+    }
+
+    /// Decode the string and return Result<u64, Base62Error>.  Checks for empty string, invalid
+    /// chars and values too large for a u64.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::{BASE36, CROCKFORD32};
+    ///
+    /// assert_eq!(BASE36.decode("LFLS").unwrap(), 1_000_000);
+    ///
+    /// // crockford normalizes ambiguous chars and ignores hyphens
+    /// assert_eq!(CROCKFORD32.decode("ygj0").unwrap(), 1_000_000);
+    /// assert_eq!(CROCKFORD32.decode("YGJ-O").unwrap(), 1_000_000);
+    /// assert_eq!(CROCKFORD32.decode("i").unwrap(), 1);
+    ///
+    /// assert!(CROCKFORD32.decode("U").is_err());
+    /// assert!(BASE36.decode("").is_err());
+    /// ```
+    pub fn decode(&self, encoded: &str) -> Result<u64, Base62Error> {
+        if encoded.is_empty() {
+            return Err(Base62Error::EmptyString);
+        }
+
+        let radix = self.radix();
+        let mut result = 0_u64;
+        let mut digits = 0;
+
+        for (index, ch) in encoded.chars().enumerate() {
+            let n = match self.decode_digit(ch, index) {
+                Ok(Some(n)) => n as u64,
+                Ok(None) => continue,
+                Err(err) => return Err(err),
+            };
+
+            result = result
+                .checked_mul(radix)
+                .and_then(|r| r.checked_add(n))
+                .ok_or(Base62Error::Overflow)?;
+            digits += 1;
+        }
+
+        if digits == 0 {
+            return Err(Base62Error::EmptyString);
+        }
+
+        Ok(result)
+    }
+
+    // return the digit value, None for skipped chars; index is the char's position for errors
+    pub(crate) fn decode_digit(&self, ch: char, index: usize) -> Result<Option<u8>, Base62Error> {
+        let value = if ch.is_ascii() {
+            self.lookup[ch as usize]
+        } else {
+            INVALID
+        };
+
+        match value {
+            INVALID => Err(Base62Error::InvalidChar { ch, index }),
+            SKIP => Ok(None),
+            n => Ok(Some(n)),
+        }
+    }
+
+    /// Return the number of chars required to encode the largest value in the range 0..=max.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::{BASE36, BASE62};
+    ///
+    /// assert_eq!(BASE62.width(61), 1);
+    /// assert_eq!(BASE62.width(62), 2);
+    /// assert_eq!(BASE36.width(u64::MAX), 13);
+    /// ```
+    pub fn width(&self, max: u64) -> usize {
+        self.encode(max).len()
+    }
+}
+
+/// Convert a value encoded with one codec to another.  When both codecs have the same radix the
+/// conversion is digit by digit, so the length and any zero padding are preserved and strings of
+/// any length (e.g., a full routing key) can be converted.  Otherwise the value is decoded to a u64
+/// and re-encoded.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::codec::{convert, BASE36, BASE62, BASE62_INVERTED};
+///
+/// let key = "YM6I7clU96YvDTCr";
+/// let inverted = convert(key, &BASE62, &BASE62_INVERTED).unwrap();
+///
+/// assert_eq!(inverted, "ym6i7CLu96yVdtcR");
+/// assert_eq!(convert(&inverted, &BASE62_INVERTED, &BASE62).unwrap(), key);
+///
+/// assert_eq!(convert("G8", &BASE62, &BASE36).unwrap(), "rs");
+/// ```
+pub fn convert(encoded: &str, from: &Codec, to: &Codec) -> Result<String, Base62Error> {
+    if from.radix() != to.radix() {
+        return from.decode(encoded).map(|n| to.encode(n));
+    }
+
+    if encoded.is_empty() {
+        return Err(Base62Error::EmptyString);
+    }
+
+    let mut converted = String::with_capacity(encoded.len());
+    for (index, ch) in encoded.chars().enumerate() {
+        if let Some(n) = from.decode_digit(ch, index)? {
+            converted.push(to.alphabet[n as usize] as char);
+        }
+    }
+
+    Ok(converted)
+}
+
+impl Default for Codec {
+    fn default() -> Self {
+        BASE62
+    }
+}
+
+impl PartialEq for Codec {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.alphabet == other.alphabet
+    }
+}
+
+impl Eq for Codec {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn radix() {
+        assert_eq!(BASE62.radix(), 62);
+        assert_eq!(BASE58.radix(), 58);
+        assert_eq!(BASE36.radix(), 36);
+        assert_eq!(CROCKFORD32.radix(), 32);
+    }
+
+    #[test]
+    fn digit_values() {
+        for codec in CODECS {
+            for (n, ch) in codec.alphabet().iter().enumerate() {
+                assert_eq!(codec.decode_digit(*ch as char, n).unwrap(), Some(n as u8));
+            }
+        }
+    }
+
+    #[test]
+    fn base58_excludes_ambiguous() {
+        for ch in ['0', 'O', 'I', 'l'] {
+            assert!(BASE58.decode_digit(ch, 0).is_err());
+        }
+    }
+
+    #[test]
+    fn ignore_case() {
+        assert_eq!(BASE36.decode("zz").unwrap(), BASE36.decode("ZZ").unwrap());
+        assert_eq!(CROCKFORD32.decode("zz").unwrap(), 1023);
+
+        // base62 is case sensitive
+        assert_ne!(BASE62.decode("zz").unwrap(), BASE62.decode("ZZ").unwrap());
+    }
+
+    #[test]
+    fn crockford_aliases() {
+        assert_eq!(CROCKFORD32.decode("O").unwrap(), 0);
+        assert_eq!(CROCKFORD32.decode("o").unwrap(), 0);
+        assert_eq!(CROCKFORD32.decode("I").unwrap(), 1);
+        assert_eq!(CROCKFORD32.decode("l").unwrap(), 1);
+        assert_eq!(CROCKFORD32.decode("L").unwrap(), 1);
+        assert_eq!(CROCKFORD32.decode("1-0").unwrap(), 32);
+
+        assert!(CROCKFORD32.decode("U").is_err());
+        assert!(CROCKFORD32.decode("-").is_err());
+    }
+
+    #[test]
+    fn invalid_char_position() {
+        assert_eq!(
+            BASE62.decode("AB~CY").unwrap_err(),
+            Base62Error::InvalidChar { ch: '~', index: 2 }
+        );
+
+        // multi-byte chars are reported by char position
+        assert_eq!(
+            BASE36.decode("aé").unwrap_err(),
+            Base62Error::InvalidChar { ch: 'é', index: 1 }
+        );
+        assert_eq!(
+            convert("0~", &BASE62, &BASE62_INVERTED).unwrap_err(),
+            Base62Error::InvalidChar { ch: '~', index: 1 }
+        );
+    }
+
+    #[test]
+    fn overflow() {
+        let max = BASE62.encode(u64::MAX);
+        assert_eq!(BASE62.decode(&max).unwrap(), u64::MAX);
+
+        let too_big = format!("{}0", max);
+        match BASE62.decode(&too_big) {
+            Err(Base62Error::Overflow) => (),
+            other => panic!("should overflow: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn encode_with_pad() {
+        assert_eq!(BASE62.encode_with_pad(0, 7), "0000000");
+        assert_eq!(BASE58.encode_with_pad(57, 2), "1z");
+        assert_eq!(BASE62.encode_with_pad(u64::MAX, 2), "LygHa16AHYF");
+    }
+
+    #[test]
+    fn convert_same_radix() {
+        assert_eq!(convert("00zZ", &BASE62, &BASE62_INVERTED).unwrap(), "00Zz");
+        assert_eq!(convert("00Zz", &BASE62_INVERTED, &BASE62).unwrap(), "00zZ");
+        assert!(convert("", &BASE62, &BASE62_INVERTED).is_err());
+        assert!(convert("ab-c", &BASE62, &BASE62_INVERTED).is_err());
+    }
+
+    #[test]
+    fn convert_radix() {
+        assert_eq!(convert("lfls", &BASE36, &BASE62).unwrap(), "4C92");
+        assert_eq!(convert("4C92", &BASE62, &CROCKFORD32).unwrap(), "YGJ0");
+    }
+
+    #[test]
+    fn round_trip() {
+        for codec in CODECS {
+            for n in [0, 1, 61, 62, 1_000, 1_000_000, u64::MAX / 3, u64::MAX] {
+                let encoded = codec.encode(n);
+                assert_eq!(codec.decode(&encoded).unwrap(), n, "{}", codec.name());
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//!
+//! Stable content hashing for model values.  `std::collections::hash_map::DefaultHasher` is not
+//! stable across rust releases, so persisted hashes would change with a toolchain upgrade.
+//!
+//! The content hash is the 64 bit [FNV-1a](http://www.isthe.com/chongo/tech/comp/fnv/) hash of
+//! the value's canonical json: the serde json serialization with object keys sorted and no
+//! whitespace.  The hash depends only on the serialized data, so it is stable across releases,
+//! platforms and map iteration order.
+//!
+
+use serde::Serialize;
+use serde_json::Value;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Return the 64 bit FNV-1a hash of the bytes.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::hash::fnv1a64;
+///
+/// assert_eq!(fnv1a64(b""), 0xcbf29ce484222325);
+/// assert_eq!(fnv1a64(b"foobar"), 0x85944171f73967e8);
+/// ```
+pub fn fnv1a64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS, |hash, b| {
+        (hash ^ *b as u64).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// Return the canonical json for the value; object keys are sorted and there is no whitespace.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::hash::canonical_json;
+/// use std::collections::HashMap;
+///
+/// let mut map = HashMap::new();
+/// map.insert("b", 2);
+/// map.insert("a", 1);
+///
+/// assert_eq!(canonical_json(&map).unwrap(), r#"{"a":1,"b":2}"#);
+/// ```
+pub fn canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    let value = serde_json::to_value(value)?;
+    let mut out = String::new();
+    write_canonical(&value, &mut out)?;
+
+    Ok(out)
+}
+
+/// Return the stable content hash of the value's canonical json.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::hash::{content_hash, fnv1a64};
+///
+/// let hash = content_hash(&"my test").unwrap();
+///
+/// assert_eq!(hash, fnv1a64(br#""my test""#));
+/// ```
+pub fn content_hash<T: Serialize + ?Sized>(value: &T) -> Result<u64, serde_json::Error> {
+    canonical_json(value).map(|json| fnv1a64(json.as_bytes()))
+}
+
+fn write_canonical(value: &Value, out: &mut String) -> Result<(), serde_json::Error> {
+    match value {
+        Value::Array(list) => {
+            out.push('[');
+            for (idx, item) in list.iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out)?;
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+
+            out.push('{');
+            for (idx, (key, item)) in entries.into_iter().enumerate() {
+                if idx > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(key)?);
+                out.push(':');
+                write_canonical(item, out)?;
+            }
+            out.push('}');
+        }
+        scalar => out.push_str(&serde_json::to_string(scalar)?),
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn fnv_vectors() {
+        assert_eq!(fnv1a64(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a64(b"a"), 0xaf63dc4c8601ec8c);
+        assert_eq!(fnv1a64(b"foobar"), 0x85944171f73967e8);
+    }
+
+    #[test]
+    fn canonical_nested() {
+        let mut inner = HashMap::new();
+        inner.insert("z", vec![1, 2]);
+        inner.insert("m", vec![]);
+
+        let mut outer = HashMap::new();
+        outer.insert("b", inner);
+        outer.insert("a", HashMap::new());
+
+        assert_eq!(
+            canonical_json(&outer).unwrap(),
+            r#"{"a":{},"b":{"m":[],"z":[1,2]}}"#
+        );
+    }
+
+    #[test]
+    fn map_order_independent() {
+        let pairs = [("one", 1), ("two", 2), ("three", 3), ("four", 4)];
+
+        let hash_map: HashMap<&str, i32> = pairs.iter().cloned().collect();
+        let btree_map: BTreeMap<&str, i32> = pairs.iter().rev().cloned().collect();
+
+        assert_eq!(
+            content_hash(&hash_map).unwrap(),
+            content_hash(&btree_map).unwrap()
+        );
+    }
+
+    #[test]
+    fn known_hash() {
+        // pinned so an accidental change to the canonical form is caught
+        assert_eq!(content_hash(&"my test").unwrap(), 0xc0cda4f6d9919c79);
+    }
+}
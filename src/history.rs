@@ -0,0 +1,390 @@
+//!
+//! Audit history for models.  `Version::update` replaces the previous timestamps and hash, so a
+//! repository can emit a `ModelEvent` for every write into an `EventSink` to keep the history:
+//! the key, the old and new version and status, and the field level changes to the serialized
+//! value.
+//!
+//! `MemorySink` keeps events in memory, e.g., for tests; `JsonLinesSink` appends one json event
+//! per line to a file.
+//!
+
+use crate::models::{Model, Status, Version};
+use crate::tenant::TenantId;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// A changed field in the serialized value.  The path is a json pointer (RFC 6901), e.g.,
+/// `/address/city`, or an empty string for the whole value.  A missing old value means the
+/// field was added and a missing new value means it was removed.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FieldChange {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub old: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub new: Option<Value>,
+}
+
+/// A model write.  Inserts have no old version or status and hard deletes have no new version
+/// or status.  The tenant is the owner of the model, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ModelEvent {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<TenantId>,
+    pub old_version: Option<Version>,
+    pub new_version: Option<Version>,
+    pub old_status: Option<Status>,
+    pub new_status: Option<Status>,
+    pub changes: Vec<FieldChange>,
+}
+
+impl ModelEvent {
+    /// Create the event for a write from the old model to the new model.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::history::ModelEvent;
+    /// use domain_keys::models::{Applied, Model};
+    /// use std::collections::BTreeMap;
+    ///
+    /// let mut value = BTreeMap::new();
+    /// value.insert("name".to_string(), "sam".to_string());
+    /// let model = Model::new(value.clone());
+    ///
+    /// value.insert("name".to_string(), "kim".to_string());
+    /// let updated = match model.apply(value) {
+    ///     Applied::Updated(updated) => updated,
+    ///     Applied::Unchanged => panic!("value should have changed"),
+    /// };
+    ///
+    /// let event = ModelEvent::new(Some(&model), Some(&updated)).unwrap();
+    ///
+    /// assert_eq!(event.key, model.key);
+    /// assert_eq!(event.changes.len(), 1);
+    /// assert_eq!(event.changes[0].path, "/name");
+    /// assert_eq!(event.changes[0].new, Some("kim".into()));
+    /// ```
+    pub fn new<T: serde::Serialize, K: fmt::Display>(
+        old: Option<&Model<T, K>>,
+        new: Option<&Model<T, K>>,
+    ) -> Result<ModelEvent, serde_json::Error> {
+        let key = match (old, new) {
+            (_, Some(model)) | (Some(model), None) => model.key.to_string(),
+            (None, None) => String::new(),
+        };
+
+        let old_value = old
+            .map(|model| serde_json::to_value(&model.value))
+            .transpose()?;
+        let new_value = new
+            .map(|model| serde_json::to_value(&model.value))
+            .transpose()?;
+
+        let mut changes = Vec::new();
+        diff_value(
+            String::new(),
+            old_value.as_ref(),
+            new_value.as_ref(),
+            &mut changes,
+        );
+
+        let tenant = match (old, new) {
+            (_, Some(model)) | (Some(model), None) => model.tenant.clone(),
+            (None, None) => None,
+        };
+
+        Ok(ModelEvent {
+            key,
+            tenant,
+            old_version: old.map(|model| model.version.clone()),
+            new_version: new.map(|model| model.version.clone()),
+            old_status: old.map(|model| model.status.clone()),
+            new_status: new.map(|model| model.status.clone()),
+            changes,
+        })
+    }
+}
+
+/// Return the field level changes between two json values.  Objects are compared field by
+/// field and arrays item by item; any other value is compared as a whole.  Items removed from
+/// the end of an array are listed last index first, then the added items in order, so the
+/// changes can be applied one after the other, e.g., as a json patch.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::history::field_changes;
+/// use serde_json::json;
+///
+/// let old = json!({"name": "sam", "tags": ["a"]});
+/// let new = json!({"name": "sam", "tags": ["a", "b"], "age": 42});
+///
+/// let paths: Vec<String> = field_changes(&old, &new).into_iter().map(|c| c.path).collect();
+///
+/// assert_eq!(paths, vec!["/age", "/tags/1"]);
+/// ```
+pub fn field_changes(old: &Value, new: &Value) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    diff_value(String::new(), Some(old), Some(new), &mut changes);
+
+    changes
+}
+
+// the one json walker, `patch::diff` builds its operations from these changes too; object
+// paths are sorted so the changes are stable
+fn diff_value(path: String, old: Option<&Value>, new: Option<&Value>, out: &mut Vec<FieldChange>) {
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+            names.sort();
+            names.dedup();
+
+            for name in names {
+                let child = format!("{}/{}", path, escape_pointer(name));
+                diff_value(child, old.get(name), new.get(name), out);
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for (idx, (old_item, new_item)) in old.iter().zip(new.iter()).enumerate() {
+                let child = format!("{}/{}", path, idx);
+                diff_value(child, Some(old_item), Some(new_item), out);
+            }
+
+            // remove from the end so the indexes stay valid
+            for idx in (new.len()..old.len()).rev() {
+                let child = format!("{}/{}", path, idx);
+                diff_value(child, old.get(idx), None, out);
+            }
+
+            for idx in old.len()..new.len() {
+                let child = format!("{}/{}", path, idx);
+                diff_value(child, None, new.get(idx), out);
+            }
+        }
+        (old, new) if old != new => out.push(FieldChange {
+            path,
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => (),
+    }
+}
+
+// json pointer escapes, RFC 6901
+pub(crate) fn escape_pointer(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+/// The destination for model events.  Sinks are shared by repositories across threads.
+pub trait EventSink: Send + Sync {
+    /// record the event
+    fn emit(&self, event: &ModelEvent) -> io::Result<()>;
+}
+
+/// An in-memory event sink.
+#[derive(Debug, Default)]
+pub struct MemorySink {
+    events: Mutex<Vec<ModelEvent>>,
+}
+
+impl MemorySink {
+    /// Create a new empty sink.
+    pub fn new() -> MemorySink {
+        MemorySink::default()
+    }
+
+    /// Return a copy of the recorded events in the order they were emitted.
+    pub fn events(&self) -> Vec<ModelEvent> {
+        self.events
+            .lock()
+            .map(|events| events.clone())
+            .unwrap_or_default()
+    }
+
+    /// Return the recorded events for the key.
+    pub fn events_for(&self, key: &str) -> Vec<ModelEvent> {
+        self.events()
+            .into_iter()
+            .filter(|event| event.key == key)
+            .collect()
+    }
+}
+
+impl EventSink for MemorySink {
+    fn emit(&self, event: &ModelEvent) -> io::Result<()> {
+        let mut events = self
+            .events
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "lock poisoned"))?;
+        events.push(event.clone());
+
+        Ok(())
+    }
+}
+
+/// An append-only event sink that writes each event as a line of json.  Each event is flushed
+/// as it's written.
+#[derive(Debug)]
+pub struct JsonLinesSink {
+    file: Mutex<File>,
+}
+
+impl JsonLinesSink {
+    /// Open the file for append, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<JsonLinesSink> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(JsonLinesSink {
+            file: Mutex::new(file),
+        })
+    }
+
+    /// Read the events from a json lines file; blank lines are skipped.
+    pub fn read<P: AsRef<Path>>(path: P) -> io::Result<Vec<ModelEvent>> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut events = Vec::new();
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            events.push(serde_json::from_str(&line)?);
+        }
+
+        Ok(events)
+    }
+}
+
+impl EventSink for JsonLinesSink {
+    fn emit(&self, event: &ModelEvent) -> io::Result<()> {
+        let mut line = serde_json::to_vec(event)?;
+        line.push(b'\n');
+
+        let mut file = self
+            .file
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "lock poisoned"))?;
+
+        // a single write keeps lines whole when several processes append to the file
+        file.write_all(&line)?;
+        file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::StatusFlags;
+    use serde_json::json;
+
+    #[test]
+    fn nested_changes() {
+        let old = json!({"name": "sam", "address": {"city": "seattle", "zip": "98101"}});
+        let new = json!({"name": "sam", "address": {"city": "tacoma"}, "a/b": 1});
+
+        let changes = field_changes(&old, &new);
+
+        assert_eq!(changes.len(), 3);
+        assert_eq!(changes[0].path, "/a~1b");
+        assert_eq!(changes[0].old, None);
+        assert_eq!(changes[1].path, "/address/city");
+        assert_eq!(changes[1].old, Some(json!("seattle")));
+        assert_eq!(changes[1].new, Some(json!("tacoma")));
+        assert_eq!(changes[2].path, "/address/zip");
+        assert_eq!(changes[2].new, None);
+    }
+
+    #[test]
+    fn scalar_changes() {
+        assert!(field_changes(&json!(1), &json!(1)).is_empty());
+
+        let changes = field_changes(&json!(1), &json!({"a": 1}));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].path, "");
+    }
+
+    #[test]
+    fn array_and_null_changes() {
+        let old = json!({"tags": ["a", "b", "c"], "note": null, "cards": [{"n": 1}]});
+        let new =
+            json!({"tags": ["x", "b"], "note": "hi", "cards": [{"n": 2}, null], "gone": null});
+
+        let changes = field_changes(&old, &new);
+        let summary: Vec<(&str, Option<&Value>, Option<&Value>)> = changes
+            .iter()
+            .map(|c| (c.path.as_str(), c.old.as_ref(), c.new.as_ref()))
+            .collect();
+
+        assert_eq!(
+            summary,
+            vec![
+                ("/cards/0/n", Some(&json!(1)), Some(&json!(2))),
+                ("/cards/1", None, Some(&Value::Null)),
+                ("/gone", None, Some(&Value::Null)),
+                ("/note", Some(&Value::Null), Some(&json!("hi"))),
+                ("/tags/0", Some(&json!("a")), Some(&json!("x"))),
+                ("/tags/2", Some(&json!("c")), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn insert_and_delete_events() {
+        let model = Model::new("my value".to_string());
+
+        let insert = ModelEvent::new(None, Some(&model)).unwrap();
+        assert_eq!(insert.key, model.key);
+        assert_eq!(insert.old_version, None);
+        assert_eq!(insert.new_status, Some(Status::New(StatusFlags::NONE)));
+        assert_eq!(insert.changes[0].new, Some(json!("my value")));
+
+        let delete = ModelEvent::new(Some(&model), None).unwrap();
+        assert_eq!(delete.key, model.key);
+        assert_eq!(delete.new_version, None);
+        assert_eq!(delete.changes[0].old, Some(json!("my value")));
+    }
+
+    #[test]
+    fn memory_sink() {
+        let sink = MemorySink::new();
+        let a = Model::new("a".to_string());
+        let b = Model::new("b".to_string());
+
+        sink.emit(&ModelEvent::new(None, Some(&a)).unwrap())
+            .unwrap();
+        sink.emit(&ModelEvent::new(None, Some(&b)).unwrap())
+            .unwrap();
+
+        assert_eq!(sink.events().len(), 2);
+        assert_eq!(sink.events_for(&b.key)[0].key, b.key);
+    }
+
+    #[test]
+    fn json_lines_sink() {
+        let path = std::env::temp_dir().join(format!("history-{}.jsonl", fastrand::u64(..)));
+        let model = Model::new("my value".to_string());
+        let event = ModelEvent::new(None, Some(&model)).unwrap();
+
+        {
+            let sink = JsonLinesSink::open(&path).unwrap();
+            sink.emit(&event).unwrap();
+        }
+
+        // reopening appends
+        let sink = JsonLinesSink::open(&path).unwrap();
+        sink.emit(&event).unwrap();
+
+        let events = JsonLinesSink::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(events, vec![event.clone(), event]);
+    }
+}
@@ -1,20 +1,95 @@
-use crate::base62::Base62;
+use crate::base62::Base62Error;
+use crate::codec::{Codec, BASE62};
+use serde::de::{self, Deserialize, Deserializer};
+use serde::{Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::str::FromStr;
 
 use std::time::{SystemTime, UNIX_EPOCH};
 
-const MAX_64: u64 = 3_521_614_606_207; // largest 7 digit from -> zzzzzzz
-const MIN_64: u64 = 14_776_336; // smallest 5 digit conversionn from -> 0010000
 const INSERT_INDEX: usize = 4;
 const ROUTE_KEY_SIZE: usize = 16;
+// route key random chars; the random number is always at least 5 chars, padded to 7
+const RANDOM_SIZE: usize = 7;
+// timestamp key random chars
+const TX_RANDOM_SIZE: usize = 3;
+// timestamps are padded to the width of 2390-01-01 in micros; 9 chars for base62
+const MAX_MICROS: u64 = 13_253_932_800_000_000;
 
 /// Define the micro timestamp
 type NanoTimeStamp = u128;
 
-#[derive(Debug)]
-pub enum DomainKeyError<'a> {
+/// Key parse errors.  InvalidBase62 carries the decode error with the char index relative to the
+/// start of the key.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::base62::Base62Error;
+/// use domain_keys::keys::{DomainKeyError, RouteKey};
+///
+/// let err = RouteKey::parse_timestamp("YM6I7cl~96YvDTCr").unwrap_err();
+/// let expected = Base62Error::InvalidChar { ch: '~', index: 7 };
+///
+/// assert_eq!(err, DomainKeyError::InvalidBase62(expected.clone()));
+/// assert_eq!(err.to_string(), "invalid key: invalid char '~' at index 7");
+/// assert_eq!(Base62Error::from(err), expected);
+///
+/// // other key errors round trip through Base62Error::Key
+/// let err = Base62Error::from(DomainKeyError::InvalidSize);
+/// assert_eq!(DomainKeyError::from(err), DomainKeyError::InvalidSize);
+///
+/// // compose with anyhow and ?
+/// fn route(key: &str) -> anyhow::Result<u8> {
+///     Ok(RouteKey::parse_route(key, 8)?)
+/// }
+/// assert!(route("~~").is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DomainKeyError {
     InvalidSize,
-    InvalidBase62(&'a str),
+    InvalidBase62(Base62Error),
     ParseError,
+    /// the key doesn't have the domain prefix
+    InvalidDomain {
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for DomainKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainKeyError::InvalidSize => write!(f, "invalid key size"),
+            DomainKeyError::InvalidBase62(err) => write!(f, "invalid key: {}", err),
+            DomainKeyError::ParseError => write!(f, "could not parse key"),
+            DomainKeyError::InvalidDomain { expected } => {
+                write!(f, "invalid key domain, expected prefix: {}", expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DomainKeyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DomainKeyError::InvalidBase62(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<Base62Error> for DomainKeyError {
+    /// Wrap the base62 error; a wrapped key error is unwrapped.
+    fn from(err: Base62Error) -> Self {
+        match err {
+            Base62Error::Key(err) => *err,
+            err => DomainKeyError::InvalidBase62(err),
+        }
+    }
 }
 
 pub struct Keys {}
@@ -63,31 +138,57 @@ impl RouteKey {
     /// assert_eq!(key.len(), 16);
     /// ```
     pub fn create() -> String {
+        let key = Self::create_with(&BASE62);
+
+        assert_eq!(key.len(), ROUTE_KEY_SIZE);
+
+        key
+    }
+
+    /// Generate a new routing key encoded with the given codec.  The key size depends on the
+    /// codec's radix, e.g., 16 chars for base62, 17 for base36 and 18 for crockford base32.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::{BASE36, CROCKFORD32};
+    /// use domain_keys::keys::RouteKey;
+    ///
+    /// let key = RouteKey::create_with(&BASE36);
+    /// assert_eq!(key.len(), RouteKey::key_size(&BASE36));
+    /// assert_eq!(key, key.to_lowercase());
+    ///
+    /// let key = RouteKey::create_with(&CROCKFORD32);
+    /// assert_eq!(key.len(), 18);
+    /// ```
+    pub fn create_with(codec: &Codec) -> String {
         // get the timestamp in micros
         let ts = (Keys::now() / 1_000) as u64;
-        let key = Base62::encode(ts);
-
-        // println!("ts: {}, enc: {}", ts, &key);
+        let key = codec.encode_with_pad(ts, codec.width(MAX_MICROS));
 
         // now the random number padded to 7 chars
-        let mut pad: String = Self::encode_with_pad(Self::gen_random());
+        let mut pad: String = Self::encode_with_pad(codec, Self::gen_random(codec));
 
         // insert the timestamp at the 6th position
         pad.insert_str(INSERT_INDEX, key.as_str());
 
-        assert_eq!(pad.len(), ROUTE_KEY_SIZE);
+        pad
+    }
 
-        pad.to_string()
+    /// Return the size of a routing key created with the given codec.
+    pub fn key_size(codec: &Codec) -> usize {
+        codec.width(MAX_MICROS) + RANDOM_SIZE
     }
 
     // return a random number between min and max to stay in the 7 character range
-    fn gen_random() -> u64 {
-        fastrand::u64(MIN_64..MAX_64)
+    fn gen_random(codec: &Codec) -> u64 {
+        let radix = codec.radix();
+        fastrand::u64(radix.pow(RANDOM_SIZE as u32 - 3)..radix.pow(RANDOM_SIZE as u32) - 1)
     }
 
     // ensure 7 characters, padded with zeros...
-    fn encode_with_pad(n: u64) -> String {
-        format!("{:0>7}", Base62::encode(n))
+    fn encode_with_pad(codec: &Codec, n: u64) -> String {
+        codec.encode_with_pad(n, RANDOM_SIZE)
     }
 
     /// Parse and return the route from the key's first two chars based on the total number of routes specified.
@@ -140,20 +241,36 @@ impl RouteKey {
     /// ```
     ///
     pub fn parse_route(key: &str, total_routes: u8) -> Result<u8, DomainKeyError> {
-        if key.len() < 2 {
-            return Err(DomainKeyError::InvalidSize);
-        }
+        Self::parse_route_with(key, total_routes, &BASE62)
+    }
+
+    /// Parse and return the route from a key created with the given codec.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::BASE58;
+    /// use domain_keys::keys::RouteKey;
+    ///
+    /// let key = RouteKey::create_with(&BASE58);
+    /// let route = RouteKey::parse_route_with(&key, 16, &BASE58).expect("should parse route");
+    ///
+    /// assert!(route < 16);
+    /// ```
+    pub fn parse_route_with(
+        key: &str,
+        total_routes: u8,
+        codec: &Codec,
+    ) -> Result<u8, DomainKeyError> {
+        let s = match key.get(..2) {
+            Some(s) => s,
+            None => return Err(DomainKeyError::InvalidSize),
+        };
         let troutes = total_routes.clamp(1, 128);
 
-        let mut s = key.to_string();
-        s.truncate(2);
+        let n = codec.decode(s)?;
 
-        if let Ok(n) = Base62::decode(&s) {
-            let route = (n % troutes as u64) as u8;
-            Ok(route)
-        } else {
-            Err(DomainKeyError::InvalidBase62(key))
-        }
+        Ok((n % troutes as u64) as u8)
     }
 
     /// Parse the timestamp from the valid routing key.
@@ -173,20 +290,38 @@ impl RouteKey {
     /// }
     /// ```
     pub fn parse_timestamp(key: &str) -> Result<u64, DomainKeyError> {
-        if key.len() != ROUTE_KEY_SIZE {
+        Self::parse_timestamp_with(key, &BASE62)
+    }
+
+    /// Parse the timestamp from a valid routing key created with the given codec.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::CROCKFORD32;
+    /// use domain_keys::keys::{Keys, RouteKey};
+    ///
+    /// let now = Keys::now() as u64 / 1000_u64;
+    /// let key = RouteKey::create_with(&CROCKFORD32);
+    /// let ts = RouteKey::parse_timestamp_with(&key, &CROCKFORD32).expect("should parse");
+    ///
+    /// assert!(now <= ts);
+    /// ```
+    pub fn parse_timestamp_with(key: &str, codec: &Codec) -> Result<u64, DomainKeyError> {
+        if key.len() != Self::key_size(codec) {
             return Err(DomainKeyError::InvalidSize);
         }
 
-        // pull the timestamp from the key, always 8 chars
-        let encoded_timestamp = &key[INSERT_INDEX..=INSERT_INDEX + 8];
-
-        // println!("key: {}, enc ts: {} ", &key, &encoded_timestamp);
+        // pull the timestamp from the key, 9 chars for base62
+        let encoded_timestamp = match key.get(INSERT_INDEX..INSERT_INDEX + codec.width(MAX_MICROS))
+        {
+            Some(s) => s,
+            None => return Err(DomainKeyError::ParseError),
+        };
 
-        if let Ok(ts) = Base62::decode(encoded_timestamp) {
-            Ok(ts)
-        } else {
-            Err(DomainKeyError::ParseError)
-        }
+        codec
+            .decode(encoded_timestamp)
+            .map_err(|err| err.offset(INSERT_INDEX).into())
     }
 }
 
@@ -196,44 +331,500 @@ impl TimeStampKey {
     /// Create a new 12 character base62 timestamp key.
     ///
     pub fn create() -> String {
+        Self::create_with(&BASE62)
+    }
+
+    /// Create a new timestamp key encoded with the given codec; 12 chars for base62.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::codec::BASE36;
+    /// use domain_keys::keys::TimeStampKey;
+    ///
+    /// let key = TimeStampKey::create_with(&BASE36);
+    ///
+    /// assert_eq!(key.len(), TimeStampKey::key_size(&BASE36));
+    /// assert!(TimeStampKey::parse_timestamp_with(&key, &BASE36).is_ok());
+    /// ```
+    pub fn create_with(codec: &Codec) -> String {
         let ts = (Keys::now() / 1_000) as u64;
 
-        let r = Self::gen_random(3);
+        let r = Self::gen_random(codec, TX_RANDOM_SIZE as u8);
 
-        let key = Base62::encode(ts);
+        let key = codec.encode_with_pad(ts, codec.width(MAX_MICROS));
 
-        format!("{}{}", key, Base62::encode(r))
+        format!("{}{}", key, codec.encode(r))
+    }
+
+    /// Return the size of a timestamp key created with the given codec.
+    pub fn key_size(codec: &Codec) -> usize {
+        codec.width(MAX_MICROS) + TX_RANDOM_SIZE
     }
 
     // return a random number the size, clamped between 3 and 5
-    fn gen_random(size: u8) -> u64 {
-        let sz = size.clamp(3, 5);
-
-        // sized for 3
-        let min_max = match sz {
-            3 => 3844..=238327_u64,
-            4 => 238328..=14776335_u64,
-            _ => 3844..=238327_u64,
-        };
+    fn gen_random(codec: &Codec, size: u8) -> u64 {
+        let sz = size.clamp(3, 5) as u32;
+        let radix = codec.radix();
 
-        fastrand::u64(min_max)
+        fastrand::u64(radix.pow(sz - 1)..=radix.pow(sz) - 1)
     }
 
     /// Parse the time from the timestamp key
     ///
     pub fn parse_timestamp(key: &str) -> Result<u64, DomainKeyError> {
-        let encoded_timestamp = &key[..=8];
+        Self::parse_timestamp_with(key, &BASE62)
+    }
 
-        // println!("key: {}, enc ts: {} ", &key, &encoded_timestamp);
+    /// Parse the time from a timestamp key created with the given codec.
+    ///
+    pub fn parse_timestamp_with(key: &str, codec: &Codec) -> Result<u64, DomainKeyError> {
+        let encoded_timestamp = match key.get(..codec.width(MAX_MICROS)) {
+            Some(s) => s,
+            None => return Err(DomainKeyError::InvalidSize),
+        };
 
-        if let Ok(ts) = Base62::decode(encoded_timestamp) {
-            Ok(ts)
-        } else {
-            Err(DomainKeyError::ParseError)
+        Ok(codec.decode(encoded_timestamp)?)
+    }
+}
+
+/// A validated key for a `Model`.  Key types serialize as a plain string and are checked when
+/// parsed or deserialized, so a model can't hold an invalid key.
+pub trait ModelKey:
+    Clone
+    + fmt::Debug
+    + fmt::Display
+    + Eq
+    + Hash
+    + Deref<Target = str>
+    + Serialize
+    + de::DeserializeOwned
+    + Send
+    + Sync
+    + 'static
+{
+    /// create a new key
+    fn generate() -> Self;
+
+    /// parse and validate the key
+    fn parse(key: &str) -> Result<Self, DomainKeyError>;
+}
+
+// every char must be a base62 digit
+fn check_chars(key: &str, offset: usize) -> Result<(), DomainKeyError> {
+    for (index, ch) in key.chars().enumerate() {
+        if BASE62.decode_digit(ch, offset + index)?.is_none() {
+            return Err(DomainKeyError::ParseError);
+        }
+    }
+
+    Ok(())
+}
+
+macro_rules! string_key {
+    ($name:ident) => {
+        // a key type can't be empty, so the default is a new key
+        impl Default for $name {
+            fn default() -> Self {
+                <$name as ModelKey>::generate()
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = DomainKeyError;
+
+            fn from_str(key: &str) -> Result<Self, Self::Err> {
+                <$name as ModelKey>::parse(key)
+            }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = DomainKeyError;
+
+            fn try_from(key: String) -> Result<Self, Self::Error> {
+                <$name as ModelKey>::parse(&key)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(key: $name) -> String {
+                key.0
+            }
+        }
+
+        impl PartialEq<str> for $name {
+            fn eq(&self, other: &str) -> bool {
+                self.0 == other
+            }
+        }
+
+        impl PartialEq<&str> for $name {
+            fn eq(&self, other: &&str) -> bool {
+                self.0 == *other
+            }
+        }
+
+        impl PartialEq<String> for $name {
+            fn eq(&self, other: &String) -> bool {
+                &self.0 == other
+            }
+        }
+
+        impl PartialEq<$name> for String {
+            fn eq(&self, other: &$name) -> bool {
+                self == &other.0
+            }
+        }
+    };
+}
+
+/// A validated 16 char base62 routing key; the default `Model` key.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::keys::{ModelKey, RouteId};
+///
+/// let key = RouteId::generate();
+/// assert_eq!(key.len(), 16);
+/// assert_eq!(RouteId::parse(&key).unwrap(), key);
+///
+/// assert!(RouteId::parse("not-a-key").is_err());
+/// assert!(serde_json::from_str::<RouteId>(r#""YM6I7cl~96YvDTCr""#).is_err());
+/// ```
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct RouteId(String);
+
+impl ModelKey for RouteId {
+    fn generate() -> Self {
+        RouteId(RouteKey::create())
+    }
+
+    fn parse(key: &str) -> Result<Self, DomainKeyError> {
+        RouteKey::parse_timestamp(key)?;
+        check_chars(key, 0)?;
+
+        Ok(RouteId(key.to_string()))
+    }
+}
+
+string_key!(RouteId);
+
+/// A validated 12 char base62 timestamp key.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeStampId(String);
+
+impl ModelKey for TimeStampId {
+    fn generate() -> Self {
+        TimeStampId(TimeStampKey::create())
+    }
+
+    fn parse(key: &str) -> Result<Self, DomainKeyError> {
+        if key.len() != TimeStampKey::key_size(&BASE62) {
+            return Err(DomainKeyError::InvalidSize);
+        }
+        check_chars(key, 0)?;
+
+        Ok(TimeStampId(key.to_string()))
+    }
+}
+
+string_key!(TimeStampId);
+
+/// The key prefix for a domain, e.g., `usr` for users; see `DomainId`.
+pub trait KeyDomain: 'static {
+    const PREFIX: &'static str;
+}
+
+/// A routing key with a domain prefix, e.g., `usr_YM6I7cl496YvDTCr`.  The prefix is checked when
+/// the key is parsed, so a user key can't be used for an order.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::keys::{DomainId, KeyDomain, ModelKey};
+///
+/// struct User;
+/// impl KeyDomain for User {
+///     const PREFIX: &'static str = "usr";
+/// }
+///
+/// struct Order;
+/// impl KeyDomain for Order {
+///     const PREFIX: &'static str = "ord";
+/// }
+///
+/// let key: DomainId<User> = DomainId::generate();
+/// assert!(key.starts_with("usr_"));
+/// assert_eq!(key.route_key().len(), 16);
+///
+/// assert!(DomainId::<User>::parse(&key).is_ok());
+/// assert!(DomainId::<Order>::parse(&key).is_err());
+/// ```
+pub struct DomainId<D> {
+    key: String,
+    domain: PhantomData<fn() -> D>,
+}
+
+impl<D: KeyDomain> DomainId<D> {
+    /// Return the routing key without the prefix, e.g., to parse the route.
+    pub fn route_key(&self) -> &str {
+        &self.key[D::PREFIX.len() + 1..]
+    }
+}
+
+impl<D: KeyDomain> ModelKey for DomainId<D> {
+    fn generate() -> Self {
+        DomainId {
+            key: format!("{}_{}", D::PREFIX, RouteKey::create()),
+            domain: PhantomData,
+        }
+    }
+
+    fn parse(key: &str) -> Result<Self, DomainKeyError> {
+        let route_key = key
+            .strip_prefix(D::PREFIX)
+            .and_then(|rest| rest.strip_prefix('_'))
+            .ok_or(DomainKeyError::InvalidDomain {
+                expected: D::PREFIX,
+            })?;
+
+        let offset = D::PREFIX.len() + 1;
+        RouteKey::parse_timestamp(route_key).map_err(|err| match err {
+            DomainKeyError::InvalidBase62(err) => DomainKeyError::InvalidBase62(err.offset(offset)),
+            err => err,
+        })?;
+        check_chars(route_key, offset)?;
+
+        Ok(DomainId {
+            key: key.to_string(),
+            domain: PhantomData,
+        })
+    }
+}
+
+// manual impls so the domain type needs no derives of its own
+
+impl<D: KeyDomain> Default for DomainId<D> {
+    fn default() -> Self {
+        DomainId::generate()
+    }
+}
+
+impl<D> Clone for DomainId<D> {
+    fn clone(&self) -> Self {
+        DomainId {
+            key: self.key.clone(),
+            domain: PhantomData,
         }
     }
 }
 
+impl<D> fmt::Debug for DomainId<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("DomainId").field(&self.key).finish()
+    }
+}
+
+impl<D> fmt::Display for DomainId<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.key)
+    }
+}
+
+impl<D> PartialEq for DomainId<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl<D> Eq for DomainId<D> {}
+
+impl<D> PartialOrd for DomainId<D> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<D> Ord for DomainId<D> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+impl<D> Hash for DomainId<D> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state)
+    }
+}
+
+impl<D> Deref for DomainId<D> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.key
+    }
+}
+
+impl<D> PartialEq<str> for DomainId<D> {
+    fn eq(&self, other: &str) -> bool {
+        self.key == other
+    }
+}
+
+impl<D> PartialEq<String> for DomainId<D> {
+    fn eq(&self, other: &String) -> bool {
+        &self.key == other
+    }
+}
+
+impl<D> PartialEq<DomainId<D>> for String {
+    fn eq(&self, other: &DomainId<D>) -> bool {
+        self == &other.key
+    }
+}
+
+impl<D: KeyDomain> FromStr for DomainId<D> {
+    type Err = DomainKeyError;
+
+    fn from_str(key: &str) -> Result<Self, Self::Err> {
+        Self::parse(key)
+    }
+}
+
+impl<D> Serialize for DomainId<D> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.key)
+    }
+}
+
+impl<'de, D: KeyDomain> Deserialize<'de> for DomainId<D> {
+    fn deserialize<De: Deserializer<'de>>(deserializer: De) -> Result<Self, De::Error> {
+        let key = String::deserialize(deserializer)?;
+        Self::parse(&key).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod model_key_tests {
+    use super::*;
+
+    struct User;
+    impl KeyDomain for User {
+        const PREFIX: &'static str = "usr";
+    }
+
+    struct Order;
+    impl KeyDomain for Order {
+        const PREFIX: &'static str = "ord";
+    }
+
+    #[test]
+    fn route_id() {
+        let key = RouteId::generate();
+        assert_eq!(key, RouteId::parse(&key).unwrap());
+        assert_eq!(key, key.to_string());
+        assert_eq!("".parse::<RouteId>(), Err(DomainKeyError::InvalidSize));
+
+        // the timestamp parses but the random chars don't
+        let mut bad = key.to_string();
+        bad.replace_range(15.., "~");
+        assert_eq!(
+            RouteId::parse(&bad),
+            Err(DomainKeyError::InvalidBase62(Base62Error::InvalidChar {
+                ch: '~',
+                index: 15
+            }))
+        );
+    }
+
+    #[test]
+    fn timestamp_id() {
+        let key = TimeStampId::generate();
+        assert_eq!(key.len(), 12);
+        assert_eq!(TimeStampId::parse(&key).unwrap(), key);
+        assert_eq!(TimeStampId::parse("abc"), Err(DomainKeyError::InvalidSize));
+    }
+
+    #[test]
+    fn domain_id() {
+        let key: DomainId<User> = DomainId::generate();
+        assert!(RouteId::parse(key.route_key()).is_ok());
+
+        assert_eq!(
+            DomainId::<Order>::parse(&key),
+            Err(DomainKeyError::InvalidDomain { expected: "ord" })
+        );
+        assert_eq!(
+            DomainId::<User>::parse(key.route_key()),
+            Err(DomainKeyError::InvalidDomain { expected: "usr" })
+        );
+
+        let bad = format!("usr_{}~", &key.route_key()[..15]);
+        match DomainId::<User>::parse(&bad) {
+            Err(DomainKeyError::InvalidBase62(Base62Error::InvalidChar { index, .. })) => {
+                assert_eq!(index, 19)
+            }
+            other => panic!("should fail: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let key: DomainId<User> = DomainId::generate();
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(json, format!("\"{}\"", key));
+        assert_eq!(serde_json::from_str::<DomainId<User>>(&json).unwrap(), key);
+        assert!(serde_json::from_str::<DomainId<Order>>(&json).is_err());
+
+        let key = RouteId::generate();
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(serde_json::from_str::<RouteId>(&json).unwrap(), key);
+    }
+}
+
 #[cfg(test)]
 mod tx_key_tests {
     use super::*;
@@ -269,7 +860,7 @@ mod tx_key_tests {
             let key = TimeStampKey::create();
 
             assert_eq!(key.len(), 12);
-            assert_eq!(table.insert(key), true);
+            assert!(table.insert(key));
         }
 
         assert_eq!(table.len(), max_tests);
@@ -277,23 +868,25 @@ mod tx_key_tests {
 
     #[test]
     fn gen_random_3() {
-        let n = TimeStampKey::gen_random(3);
+        let n = TimeStampKey::gen_random(&BASE62, 3);
 
         assert!(n >= 3844);
         assert!(n <= 238327);
 
-        match n {
-            3844..=238327 => assert!(true),
-            _ => panic!("{} is not in range", n),
-        }
+        assert!((3844..=238327).contains(&n), "{} is not in range", n);
     }
 }
 
 #[cfg(test)]
 mod route_key_tests {
     use super::*;
+    use crate::base62::Base62;
+    use crate::codec::CODECS;
     use std::collections::HashSet;
 
+    const MAX_64: u64 = 3_521_614_606_207; // largest 7 digit from -> zzzzzzz
+    const MIN_64: u64 = 14_776_336; // smallest 5 digit conversionn from -> 0010000
+
     #[test]
     fn parse_timestamp() {
         let now = Keys::now() as u64 / 1000_u64;
@@ -306,8 +899,6 @@ mod route_key_tests {
         } else {
             panic!("not a valid timestamp");
         }
-
-        assert!(true);
     }
 
     #[test]
@@ -316,16 +907,14 @@ mod route_key_tests {
 
         if let Ok(ts) = RouteKey::parse_timestamp(&key) {
             panic!("this key should fail: {} -> {}", &key, ts);
-        } else {
-            assert!(true);
         }
     }
 
     #[test]
     fn random_number_in_range() {
         for _ in 0..10 {
-            assert!(RouteKey::gen_random() >= MIN_64);
-            assert!(RouteKey::gen_random() <= MAX_64);
+            assert!(RouteKey::gen_random(&BASE62) >= MIN_64);
+            assert!(RouteKey::gen_random(&BASE62) <= MAX_64);
         }
     }
 
@@ -336,8 +925,7 @@ mod route_key_tests {
 
         // create fake keys between 00 and zz
         let keys: Vec<String> = (0..3843_u64)
-            .into_iter()
-            .map(|n| Base62::encode(n))
+            .map(Base62::encode)
             .map(|s| format!("{:0>2}", s))
             .collect();
 
@@ -361,8 +949,7 @@ mod route_key_tests {
 
         // create fake keys between 00 and zz
         let keys: Vec<String> = (0..3843_u64)
-            .into_iter()
-            .map(|n| Base62::encode(n))
+            .map(Base62::encode)
             .map(|s| format!("{:0>2}", s))
             .collect();
 
@@ -402,12 +989,12 @@ mod route_key_tests {
         // test max, min and halfway point
         [MAX_64, MIN_64, MAX_64 / 2]
             .iter()
-            .map(|x| RouteKey::encode_with_pad(*x))
+            .map(|x| RouteKey::encode_with_pad(&BASE62, *x))
             .for_each(|s| assert_eq!(s.len(), 7));
 
         // test the formats for min and max
-        assert_eq!(RouteKey::encode_with_pad(MIN_64), "0010000");
-        assert_eq!(RouteKey::encode_with_pad(MAX_64), "zzzzzzz");
+        assert_eq!(RouteKey::encode_with_pad(&BASE62, MIN_64), "0010000");
+        assert_eq!(RouteKey::encode_with_pad(&BASE62, MAX_64), "zzzzzzz");
     }
 
     #[test]
@@ -426,9 +1013,28 @@ mod route_key_tests {
         for _ in 0..max_tests {
             let key = RouteKey::create();
             assert_eq!(key.len(), ROUTE_KEY_SIZE);
-            assert_eq!(table.insert(key), true);
+            assert!(table.insert(key));
         }
 
         assert_eq!(table.len(), max_tests);
     }
+
+    #[test]
+    fn create_with_codecs() {
+        for codec in CODECS {
+            let now = Keys::now() as u64 / 1000_u64;
+            let key = RouteKey::create_with(&codec);
+
+            assert_eq!(key.len(), RouteKey::key_size(&codec), "{}", codec.name());
+            assert!(key.bytes().all(|b| codec.alphabet().contains(&b)));
+
+            let ts = RouteKey::parse_timestamp_with(&key, &codec).unwrap();
+            assert!(ts >= now);
+
+            let route = RouteKey::parse_route_with(&key, 24, &codec).unwrap();
+            assert!(route < 24);
+        }
+
+        assert_eq!(RouteKey::key_size(&BASE62), ROUTE_KEY_SIZE);
+    }
 }
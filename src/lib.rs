@@ -1,6 +1,18 @@
 #![doc = include_str!("../README.md")]
 
 pub mod base62;
+pub mod codec;
 pub mod config;
+pub mod hash;
+pub mod history;
 pub mod keys;
 pub mod models;
+pub mod patch;
+pub mod projection;
+pub mod redact;
+pub mod repository;
+pub mod schema;
+pub mod stream;
+pub mod tenant;
+pub mod validation;
+pub mod wire;
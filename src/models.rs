@@ -1,14 +1,28 @@
-use crate::keys::RouteKey;
-use anyhow::Result;
+use crate::hash::content_hash;
+use crate::keys::{ModelKey, RouteId};
+use crate::projection::{Projection, ProjectionError};
+use crate::tenant::TenantId;
+use crate::validation::FieldError;
 /// base data models
 use chrono::naive::NaiveDateTime;
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use serde_derive::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::hash::{Hash, Hasher};
+use std::fmt;
+
+/// `#[derive(DomainModel)]`, with the `derive` feature; see the `domain_keys_derive` crate.
+#[cfg(feature = "derive")]
+pub use domain_keys_derive::DomainModel;
 
 pub type TS = NaiveDateTime;
 
+/// The schema version of new values, and of models stored without one.
+pub const FIRST_SCHEMA: u16 = 1;
+
+fn first_schema() -> u16 {
+    FIRST_SCHEMA
+}
+
 fn get_now() -> TS {
     Utc::now().naive_utc()
 }
@@ -40,100 +54,1126 @@ impl Version {
             hash: new_hash,
         }
     }
+
+    /// Compare and swap: return the updated version only if the caller read this version's
+    /// update count, otherwise another writer got here first and a conflict is returned.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::{Version, VersionConflict};
+    ///
+    /// let stored = Version::new(1);
+    /// let updated = stored.compare_and_update(0, 2).unwrap();
+    /// assert_eq!(updated.update_count, 1);
+    ///
+    /// // a second writer that also read update count 0 is rejected
+    /// let err = updated.compare_and_update(0, 3).unwrap_err();
+    /// assert_eq!(err, VersionConflict { expected: 0, actual: 1 });
+    /// ```
+    pub fn compare_and_update(
+        &self,
+        expected_update_count: u64,
+        new_hash: u64,
+    ) -> Result<Version, VersionConflict> {
+        if self.update_count != expected_update_count {
+            return Err(VersionConflict {
+                expected: expected_update_count,
+                actual: self.update_count,
+            });
+        }
+
+        Ok(self.update(new_hash))
+    }
+}
+
+/// An optimistic concurrency conflict; the update count the writer read doesn't match the
+/// stored version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VersionConflict {
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "version conflict, expected update count {} but found {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+/// Status flags carried by every `Status` variant.  The low four bits are defined by this crate
+/// and the high four bits (`DOMAIN_MASK`) are free for domain specific flags.  Flags serialize as
+/// the plain u8, so stored `{"t": "Active", "c": 128}` values keep deserializing.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::models::{Status, StatusFlags};
+///
+/// let flags = StatusFlags::VERIFIED | StatusFlags::LOCKED;
+/// let status = Status::Active(flags);
+///
+/// assert!(status.flags().contains(StatusFlags::LOCKED));
+/// assert!(!status.flags().contains(StatusFlags::FLAGGED));
+/// assert_eq!(flags.to_string(), "verified|locked");
+///
+/// let json = serde_json::to_string(&status).unwrap();
+/// assert_eq!(json, r#"{"t":"Active","c":6}"#);
+/// ```
+#[derive(
+    Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize,
+)]
+#[serde(transparent)]
+pub struct StatusFlags(u8);
+
+impl StatusFlags {
+    /// no flags set
+    pub const NONE: StatusFlags = StatusFlags(0);
+    /// flagged for review, e.g., reported content or suspicious activity
+    pub const FLAGGED: StatusFlags = StatusFlags(0x01);
+    /// verified by an external check, e.g., a confirmed email
+    pub const VERIFIED: StatusFlags = StatusFlags(0x02);
+    /// locked against changes by the owner
+    pub const LOCKED: StatusFlags = StatusFlags(0x04);
+    /// requires manual attention by an operator
+    pub const ESCALATED: StatusFlags = StatusFlags(0x08);
+    /// the bits reserved for domain specific flags
+    pub const DOMAIN_MASK: u8 = 0xf0;
+
+    const NAMES: [(StatusFlags, &'static str); 4] = [
+        (StatusFlags::FLAGGED, "flagged"),
+        (StatusFlags::VERIFIED, "verified"),
+        (StatusFlags::LOCKED, "locked"),
+        (StatusFlags::ESCALATED, "escalated"),
+    ];
+
+    /// Create flags from raw bits; all bits are kept.
+    pub const fn from_bits(bits: u8) -> StatusFlags {
+        StatusFlags(bits)
+    }
+
+    /// Create a domain specific flag from its index 0..=3 in the domain bits.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::StatusFlags;
+    ///
+    /// const PREMIUM: StatusFlags = StatusFlags::domain(0);
+    ///
+    /// assert_eq!(PREMIUM.bits(), 0x10);
+    /// ```
+    pub const fn domain(index: u8) -> StatusFlags {
+        assert!(index < 4, "domain flag index must be 0..=3");
+        StatusFlags(0x10 << index)
+    }
+
+    /// Return the raw bits.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Return true if no flags are set.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Return true if all of the other flags are set.
+    pub const fn contains(self, other: StatusFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Return the flags with other set.
+    pub const fn with(self, other: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 | other.0)
+    }
+
+    /// Return the flags with other cleared.
+    pub const fn without(self, other: StatusFlags) -> StatusFlags {
+        StatusFlags(self.0 & !other.0)
+    }
+}
+
+impl std::ops::BitOr for StatusFlags {
+    type Output = StatusFlags;
+
+    fn bitor(self, other: StatusFlags) -> StatusFlags {
+        self.with(other)
+    }
+}
+
+impl fmt::Display for StatusFlags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<String> = StatusFlags::NAMES
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, name)| name.to_string())
+            .collect();
+
+        for index in 0..4 {
+            if self.contains(StatusFlags::domain(index)) {
+                names.push(format!("domain{}", index));
+            }
+        }
+
+        if names.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", names.join("|"))
+        }
+    }
 }
 
+/// The model status; every variant carries its `StatusFlags`.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
 #[serde(tag = "t", content = "c")]
 pub enum Status {
-    New(u8),
-    Pending(u8),
-    Active(u8),
-    Inactive(u8),
-    Processed(u8),
-    Blocked(u8),
-    Deleted(u8),
+    New(StatusFlags),
+    Pending(StatusFlags),
+    Active(StatusFlags),
+    Inactive(StatusFlags),
+    Processed(StatusFlags),
+    Blocked(StatusFlags),
+    Deleted(StatusFlags),
 }
 
 impl Default for Status {
     fn default() -> Self {
-        Status::New(0)
+        Status::New(StatusFlags::NONE)
+    }
+}
+
+impl Status {
+    /// Create a status of the given kind with flags.
+    pub fn new(kind: StatusKind, flags: StatusFlags) -> Status {
+        match kind {
+            StatusKind::New => Status::New(flags),
+            StatusKind::Pending => Status::Pending(flags),
+            StatusKind::Active => Status::Active(flags),
+            StatusKind::Inactive => Status::Inactive(flags),
+            StatusKind::Processed => Status::Processed(flags),
+            StatusKind::Blocked => Status::Blocked(flags),
+            StatusKind::Deleted => Status::Deleted(flags),
+        }
+    }
+
+    /// Return the status flags.
+    pub fn flags(&self) -> StatusFlags {
+        match self {
+            Status::New(flags)
+            | Status::Pending(flags)
+            | Status::Active(flags)
+            | Status::Inactive(flags)
+            | Status::Processed(flags)
+            | Status::Blocked(flags)
+            | Status::Deleted(flags) => *flags,
+        }
+    }
+
+    /// Return the same status kind with new flags.
+    pub fn with_flags(&self, flags: StatusFlags) -> Status {
+        Status::new(self.kind(), flags)
+    }
+
+    /// Return the status kind without the payload.
+    pub fn kind(&self) -> StatusKind {
+        match self {
+            Status::New(_) => StatusKind::New,
+            Status::Pending(_) => StatusKind::Pending,
+            Status::Active(_) => StatusKind::Active,
+            Status::Inactive(_) => StatusKind::Inactive,
+            Status::Processed(_) => StatusKind::Processed,
+            Status::Blocked(_) => StatusKind::Blocked,
+            Status::Deleted(_) => StatusKind::Deleted,
+        }
+    }
+}
+
+/// The kind of a `Status` without its payload; used as the index into a transition table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum StatusKind {
+    New,
+    Pending,
+    Active,
+    Inactive,
+    Processed,
+    Blocked,
+    Deleted,
+}
+
+impl StatusKind {
+    /// All status kinds.
+    pub const ALL: [StatusKind; 7] = [
+        StatusKind::New,
+        StatusKind::Pending,
+        StatusKind::Active,
+        StatusKind::Inactive,
+        StatusKind::Processed,
+        StatusKind::Blocked,
+        StatusKind::Deleted,
+    ];
+}
+
+/// A table of allowed status transitions.  A move to the same kind (a payload change) is always
+/// allowed.  The default table is:
+///
+/// * New -> Pending, Active
+/// * Pending -> Active, Blocked
+/// * Active <-> Inactive, Active -> Processed, Blocked
+/// * Blocked -> Active
+/// * any status -> Deleted; Deleted is terminal
+///
+/// Leaving Deleted is a restore, not a transition, and has its own targets: the default table
+/// allows Deleted -> New, Pending, Active, Inactive, Processed and Blocked only via
+/// `Model::restore`.  A domain that must not restore uses `without_restore`.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::models::{StatusKind, Transitions};
+///
+/// let transitions = Transitions::default();
+/// assert!(transitions.is_allowed(StatusKind::New, StatusKind::Pending));
+/// assert!(!transitions.is_allowed(StatusKind::Deleted, StatusKind::Active));
+/// assert!(transitions.is_restore_allowed(StatusKind::Active));
+///
+/// // a custom table for a domain where processed items can be re-opened
+/// let transitions = Transitions::default().allow(StatusKind::Processed, StatusKind::Active);
+/// assert!(transitions.is_allowed(StatusKind::Processed, StatusKind::Active));
+///
+/// // a domain where deletes are final
+/// let transitions = Transitions::default().without_restore();
+/// assert!(!transitions.is_restore_allowed(StatusKind::Active));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Transitions {
+    allowed: [[bool; 7]; 7],
+    restore: [bool; 7],
+}
+
+impl Transitions {
+    /// Create an empty table; only same-kind moves are allowed and nothing can be restored.
+    pub fn new() -> Transitions {
+        Transitions {
+            allowed: [[false; 7]; 7],
+            restore: [false; 7],
+        }
+    }
+
+    /// Allow the move from -> to.
+    pub fn allow(mut self, from: StatusKind, to: StatusKind) -> Transitions {
+        self.allowed[from as usize][to as usize] = true;
+        self
+    }
+
+    /// Disallow the move from -> to.
+    pub fn deny(mut self, from: StatusKind, to: StatusKind) -> Transitions {
+        self.allowed[from as usize][to as usize] = false;
+        self
+    }
+
+    /// Allow the move to this status from any status.
+    pub fn allow_from_any(mut self, to: StatusKind) -> Transitions {
+        for from in StatusKind::ALL {
+            self.allowed[from as usize][to as usize] = true;
+        }
+        self
+    }
+
+    /// Return true if the move is allowed.
+    pub fn is_allowed(&self, from: StatusKind, to: StatusKind) -> bool {
+        from == to || self.allowed[from as usize][to as usize]
+    }
+
+    /// Allow restoring a deleted model to this status.
+    pub fn allow_restore(mut self, to: StatusKind) -> Transitions {
+        self.restore[to as usize] = true;
+        self
+    }
+
+    /// Disallow restoring a deleted model to this status.
+    pub fn deny_restore(mut self, to: StatusKind) -> Transitions {
+        self.restore[to as usize] = false;
+        self
+    }
+
+    /// Disallow restoring deleted models to any status.
+    pub fn without_restore(mut self) -> Transitions {
+        self.restore = [false; 7];
+        self
+    }
+
+    /// Return true if a deleted model may be restored to this status; never to Deleted.
+    pub fn is_restore_allowed(&self, to: StatusKind) -> bool {
+        to != StatusKind::Deleted && self.restore[to as usize]
+    }
+}
+
+impl Default for Transitions {
+    fn default() -> Self {
+        use StatusKind::*;
+
+        Transitions::new()
+            .allow(New, Pending)
+            .allow(New, Active)
+            .allow(Pending, Active)
+            .allow(Pending, Blocked)
+            .allow(Active, Inactive)
+            .allow(Active, Processed)
+            .allow(Active, Blocked)
+            .allow(Inactive, Active)
+            .allow(Blocked, Active)
+            .allow_from_any(Deleted)
+            .allow_restore(New)
+            .allow_restore(Pending)
+            .allow_restore(Active)
+            .allow_restore(Inactive)
+            .allow_restore(Processed)
+            .allow_restore(Blocked)
     }
 }
 
-#[derive(Debug, Default, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
-pub struct Model<T> {
-    pub key: Cow<'static, str>,
+/// An illegal status transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionError {
+    pub from: StatusKind,
+    pub to: StatusKind,
+}
+
+impl fmt::Display for TransitionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "illegal status transition: {:?} -> {:?}",
+            self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
+/// A domain value with its key, version and status.  The key type defaults to `RouteId`; use a
+/// `DomainId` for domain prefixed keys.  Keys are validated types, so a model can't be created,
+/// or deserialized, with an invalid or wrong-domain key.
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(bound(
+    serialize = "T: serde::Serialize, K: serde::Serialize",
+    deserialize = "T: serde::Deserialize<'de>, K: serde::Deserialize<'de>"
+))]
+pub struct Model<T, K = RouteId> {
+    pub key: K,
     pub version: Version,
+    /// the value's schema version; models stored before it was tracked are version 1, see
+    /// [`crate::schema`]
+    #[serde(default = "first_schema")]
+    pub schema: u16,
     pub status: Status,
     pub value: T,
+    /// set when the model is soft deleted; see `Model::mark_deleted`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<TS>,
+    /// the tenant that owns the model in a multi-tenant store; see [`crate::tenant`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<TenantId>,
+    /// when a short-lived model, e.g., a session, expires; see `Model::is_expired`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<TS>,
 }
 
-impl<T: Default + Clone + PartialOrd + Ord + PartialEq + Eq + Hash> Model<T> {
-    /// calculate the new hash value for this model
-    pub fn calc_hash(value: &T) -> u64 {
-        use std::collections::hash_map::DefaultHasher;
+/// The default value with a new key, e.g., for struct update syntax in tests.  Like the
+/// fields, the version is the default, so its hash isn't the value's hash; use `Model::new` for
+/// a model that's ready to store.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::models::{Model, FIRST_SCHEMA};
+///
+/// let model: Model<String> = Model::default();
+///
+/// assert_eq!(model.value, "");
+/// assert_eq!(model.key.len(), 16);
+/// assert_eq!(model.schema, FIRST_SCHEMA);
+/// assert_eq!(model.version.update_count, 0);
+/// ```
+impl<T: Default, K: Default> Default for Model<T, K> {
+    fn default() -> Self {
+        Model {
+            key: K::default(),
+            version: Version::default(),
+            schema: FIRST_SCHEMA,
+            status: Status::default(),
+            value: T::default(),
+            deleted_at: None,
+            tenant: None,
+            expires_at: None,
+        }
+    }
+}
 
-        let mut hasher = DefaultHasher::new();
-        value.hash(&mut hasher);
+impl<T: serde::Serialize> Model<T> {
+    /// calculate the new hash value for this model; the hash is the stable content hash of the
+    /// value's canonical json, see [`crate::hash`].  The hash doesn't depend on the key type.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized to json, e.g., a map with non-string keys; use
+    /// `try_calc_hash` for values that may not.
+    pub fn calc_hash(value: &T) -> u64 {
+        Model::try_calc_hash(value).expect("model value must serialize to json")
+    }
 
-        hasher.finish()
+    /// Calculate the hash like `calc_hash`, or return the error if the value can't be
+    /// serialized to json.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::Model;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut points = HashMap::new();
+    /// points.insert((1, 2), "a".to_string());
+    ///
+    /// assert!(Model::try_calc_hash(&points).is_err());
+    /// assert!(Model::try_new(points).is_err());
+    /// ```
+    pub fn try_calc_hash(value: &T) -> Result<u64, serde_json::Error> {
+        content_hash(value)
     }
 
-    /// create a new model from the value; calc the new hash
+    /// create a new model from the value with a new routing key; calc the new hash.  The model
+    /// is at `FIRST_SCHEMA`; create domain models with `new_domain`, which uses the domain's
+    /// schema version, or set it with `with_schema`.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized to json; see `try_new`.
     pub fn new(value: T) -> Model<T> {
-        let hash = Model::calc_hash(&value);
-        let version = Version::new(hash);
-        let key: Cow<'static, str> = Cow::Owned(RouteKey::create());
+        Model::new_keyed(value)
+    }
 
-        Model {
+    /// Create a new model like `new`, or return the error if the value can't be hashed.
+    pub fn try_new(value: T) -> Result<Model<T>, serde_json::Error> {
+        Model::try_new_keyed(value)
+    }
+}
+
+impl<T: serde::Serialize, K: ModelKey> Model<T, K> {
+    /// Create a new model from the value with a new key of the model's key type.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized to json; see `try_new_keyed`.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::keys::{DomainId, KeyDomain};
+    /// use domain_keys::models::Model;
+    ///
+    /// struct User;
+    /// impl KeyDomain for User {
+    ///     const PREFIX: &'static str = "usr";
+    /// }
+    ///
+    /// let model: Model<String, DomainId<User>> = Model::new_keyed("sam".to_string());
+    /// assert!(model.key.starts_with("usr_"));
+    ///
+    /// let json = serde_json::to_string(&model).unwrap();
+    /// assert_eq!(serde_json::from_str::<Model<String, DomainId<User>>>(&json).unwrap(), model);
+    /// ```
+    pub fn new_keyed(value: T) -> Model<T, K> {
+        Model::with_key(K::generate(), value)
+    }
+
+    /// Create a new model like `new_keyed`, or return the error if the value can't be hashed.
+    pub fn try_new_keyed(value: T) -> Result<Model<T, K>, serde_json::Error> {
+        Model::try_with_key(K::generate(), value)
+    }
+
+    /// Create a new model from the key and value; calc the new hash.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized to json; see `try_with_key`.
+    pub fn with_key(key: K, value: T) -> Model<T, K> {
+        Model::try_with_key(key, value).expect("model value must serialize to json")
+    }
+
+    /// Create a new model like `with_key`, or return the error if the value can't be hashed.
+    pub fn try_with_key(key: K, value: T) -> Result<Model<T, K>, serde_json::Error> {
+        let hash = Model::try_calc_hash(&value)?;
+
+        Ok(Model {
             key,
-            version,
-            status: Status::New(0),
+            version: Version::new(hash),
+            schema: FIRST_SCHEMA,
+            status: Status::New(StatusFlags::NONE),
             value,
+            deleted_at: None,
+            tenant: None,
+            expires_at: None,
+        })
+    }
+
+    /// Migration helper: return a copy of the model with its hash recalculated, or None if the
+    /// stored hash is current.  The update count and timestamps are unchanged because the value
+    /// didn't change.  Use this to re-hash models stored with an older hash.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized to json; see `try_rehash`.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::Model;
+    ///
+    /// let model = Model::new("my value".to_string());
+    /// assert!(model.rehash().is_none());
+    ///
+    /// let mut legacy = model.clone();
+    /// legacy.version.hash = 42;
+    ///
+    /// let migrated = legacy.rehash().expect("should re-hash");
+    /// assert_eq!(migrated.version, model.version);
+    /// ```
+    pub fn rehash(&self) -> Option<Model<T, K>>
+    where
+        T: Clone,
+    {
+        self.try_rehash()
+            .expect("model value must serialize to json")
+    }
+
+    /// Re-hash like `rehash`, or return the error if the value can't be hashed.
+    pub fn try_rehash(&self) -> Result<Option<Model<T, K>>, serde_json::Error>
+    where
+        T: Clone,
+    {
+        let hash = Model::try_calc_hash(&self.value)?;
+        if hash == self.version.hash {
+            return Ok(None);
         }
+
+        let mut model = Model::from_model(self);
+        model.version.hash = hash;
+
+        Ok(Some(model))
     }
 
+    /// Apply a new value to the model.  If the value's hash matches the current version hash the
+    /// value is unchanged and no write is needed; otherwise return the updated model with a
+    /// bumped version.
+    ///
+    /// # Panics
+    ///
+    /// If the value can't be serialized to json; see `try_apply`.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::{Applied, Model};
+    ///
+    /// let model = Model::new("my value".to_string());
+    ///
+    /// assert_eq!(model.apply("my value".to_string()), Applied::Unchanged);
+    ///
+    /// if let Applied::Updated(updated) = model.apply("new value".to_string()) {
+    ///     assert_eq!(updated.value, "new value");
+    ///     assert_eq!(updated.version.update_count, 1);
+    /// } else {
+    ///     panic!("value should have changed");
+    /// }
+    /// ```
+    pub fn apply(&self, value: T) -> Applied<T, K> {
+        self.try_apply(value)
+            .expect("model value must serialize to json")
+    }
+
+    /// Apply like `apply`, or return the error if the value can't be hashed.
+    pub fn try_apply(&self, value: T) -> Result<Applied<T, K>, serde_json::Error> {
+        let hash = Model::try_calc_hash(&value)?;
+        if hash == self.version.hash {
+            return Ok(Applied::Unchanged);
+        }
+
+        Ok(Applied::Updated(Model {
+            key: self.key.clone(),
+            version: self.version.update(hash),
+            schema: self.schema,
+            status: self.status.clone(),
+            value,
+            deleted_at: self.deleted_at,
+            tenant: self.tenant.clone(),
+            expires_at: self.expires_at,
+        }))
+    }
+}
+
+impl<T, K> Model<T, K> {
+    /// Return true if the model is soft deleted.
+    pub fn is_deleted(&self) -> bool {
+        self.status.kind() == StatusKind::Deleted
+    }
+
+    /// Return the model owned by the tenant.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::Model;
+    /// use domain_keys::tenant::TenantId;
+    ///
+    /// let acme = TenantId::new("acme").unwrap();
+    /// let model = Model::new("my value".to_string()).with_tenant(acme.clone());
+    ///
+    /// assert!(model.belongs_to(Some(&acme)));
+    /// assert!(!model.belongs_to(None));
+    /// assert_eq!(model.scoped_key(), format!("acme:{}", model.key));
+    /// ```
+    pub fn with_tenant(mut self, tenant: TenantId) -> Model<T, K> {
+        self.tenant = Some(tenant);
+        self
+    }
+
+    /// Return the model at the value's schema version; see [`crate::schema`].  Domain models
+    /// created with `new_domain` use `DomainModel::SCHEMA`.
+    pub fn with_schema(mut self, schema: u16) -> Model<T, K> {
+        self.schema = schema;
+        self
+    }
+
+    /// Return the model set to expire after the time to live, e.g., for a new session.  The
+    /// version isn't bumped; use `extend` to change the expiry of a stored model.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use chrono::{Duration, Utc};
+    /// use domain_keys::models::Model;
+    ///
+    /// let session = Model::new("token".to_string()).with_ttl(Duration::minutes(30));
+    /// let now = Utc::now().naive_utc();
+    ///
+    /// assert!(!session.is_expired(now));
+    /// assert!(session.is_expired(now + Duration::minutes(31)));
+    /// assert!(session.ttl(now).unwrap() <= Duration::minutes(30));
+    ///
+    /// let extended = session.extend(Duration::minutes(30));
+    /// assert!(!extended.is_expired(now + Duration::minutes(31)));
+    /// assert_eq!(extended.version.update_count, 1);
+    /// ```
+    pub fn with_ttl(mut self, ttl: Duration) -> Model<T, K> {
+        self.expires_at = Some(get_now() + ttl);
+        self
+    }
+
+    /// Return true if the model has an expiry at or before now.
+    pub fn is_expired(&self, now: TS) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= now)
+    }
+
+    /// Return the time to live from now, or None if the model doesn't expire.  An expired model
+    /// has a zero ttl.  Redis backends can pass the ttl with the write, e.g., `SET key value PX
+    /// ttl.num_milliseconds()`, so expired models are evicted by the store.
+    pub fn ttl(&self, now: TS) -> Option<Duration> {
+        self.expires_at
+            .map(|expires_at| std::cmp::max(expires_at - now, Duration::zero()))
+    }
+
+    // check the move to the status against the table and return the deletion time after it
+    pub(crate) fn transition_deleted_at(
+        &self,
+        to: &Status,
+        transitions: &Transitions,
+    ) -> Result<Option<TS>, TransitionError> {
+        let from = self.status.kind();
+        if !transitions.is_allowed(from, to.kind()) {
+            return Err(TransitionError {
+                from,
+                to: to.kind(),
+            });
+        }
+
+        Ok(match (from, to.kind()) {
+            (StatusKind::Deleted, StatusKind::Deleted) => self.deleted_at,
+            (_, StatusKind::Deleted) => Some(get_now()),
+            _ => None,
+        })
+    }
+
+    // like transition_deleted_at, but a move out of Deleted is a restore and is checked against
+    // the table's restore targets; used by repositories, which can't tell a restore from a move
+    pub(crate) fn stored_deleted_at(
+        &self,
+        to: &Status,
+        transitions: &Transitions,
+    ) -> Result<Option<TS>, TransitionError> {
+        let from = self.status.kind();
+        if from != StatusKind::Deleted || to.kind() == StatusKind::Deleted {
+            return self.transition_deleted_at(to, transitions);
+        }
+
+        if !transitions.is_restore_allowed(to.kind()) {
+            return Err(TransitionError {
+                from,
+                to: to.kind(),
+            });
+        }
+
+        Ok(None)
+    }
+
+    /// Return true if the model is owned by the tenant, or has no tenant when `tenant` is None.
+    pub fn belongs_to(&self, tenant: Option<&TenantId>) -> bool {
+        self.tenant.as_ref() == tenant
+    }
+
+    /// Return the key namespaced by the model's tenant, or the plain key without a tenant.
+    pub fn scoped_key(&self) -> String
+    where
+        K: fmt::Display,
+    {
+        match &self.tenant {
+            Some(tenant) => tenant.scoped_key(&self.key.to_string()),
+            None => self.key.to_string(),
+        }
+    }
+}
+
+impl<T: Clone, K: Clone> Model<T, K> {
     /// create a copy from the model
-    pub fn from_model(model: &Model<T>) -> Model<T> {
+    pub fn from_model(model: &Model<T, K>) -> Model<T, K> {
         Model {
             key: model.key.clone(),
             version: model.version.clone(),
+            schema: model.schema,
             status: model.status.clone(),
             value: model.value.clone(),
+            deleted_at: model.deleted_at,
+            tenant: model.tenant.clone(),
+            expires_at: model.expires_at,
         }
     }
 
-    /// create a new model from existing values of key, version, status and value
-    pub fn create_model(key: String, version: &Version, status: &Status, value: &T) -> Model<T> {
+    /// create a new model from existing values of key, version, status and value at
+    /// `FIRST_SCHEMA`; set the value's schema with `with_schema`
+    pub fn create_model(key: K, version: &Version, status: &Status, value: &T) -> Model<T, K> {
         Model {
-            key: Cow::Owned(key),
+            key,
             version: version.clone(),
+            schema: FIRST_SCHEMA,
             status: status.clone(),
             value: value.clone(),
+            deleted_at: None,
+            tenant: None,
+            expires_at: None,
+        }
+    }
+
+    /// Move the model to the new status if the table allows it and return the model with a
+    /// bumped version; the value hash is unchanged.
+    pub fn transition_with(
+        &self,
+        to: Status,
+        transitions: &Transitions,
+    ) -> Result<Model<T, K>, TransitionError> {
+        let deleted_at = self.transition_deleted_at(&to, transitions)?;
+
+        Ok(Model {
+            key: self.key.clone(),
+            version: self.version.update(self.version.hash),
+            schema: self.schema,
+            status: to,
+            value: self.value.clone(),
+            deleted_at,
+            tenant: self.tenant.clone(),
+            expires_at: self.expires_at,
+        })
+    }
+
+    /// Soft delete: return the model with a `Deleted` status, keeping the current flags, and the
+    /// deletion time.  Deletion is allowed from any status, so the transition table is not
+    /// consulted.  A model that is already deleted is returned unchanged.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::{Model, Status, StatusFlags, Transitions};
+    ///
+    /// let model = Model::new("my value".to_string());
+    /// let deleted = model.mark_deleted();
+    ///
+    /// assert!(deleted.is_deleted());
+    /// assert!(deleted.deleted_at.is_some());
+    /// assert_eq!(deleted.version.update_count, 1);
+    ///
+    /// let restored = deleted
+    ///     .restore_with(Status::Inactive(StatusFlags::NONE), &Transitions::default())
+    ///     .unwrap();
+    /// assert!(!restored.is_deleted());
+    /// assert_eq!(restored.deleted_at, None);
+    /// ```
+    pub fn mark_deleted(&self) -> Model<T, K> {
+        if self.is_deleted() {
+            return self.clone();
+        }
+
+        Model {
+            key: self.key.clone(),
+            version: self.version.update(self.version.hash),
+            schema: self.schema,
+            status: Status::Deleted(self.status.flags()),
+            value: self.value.clone(),
+            deleted_at: Some(get_now()),
+            tenant: self.tenant.clone(),
+            expires_at: self.expires_at,
+        }
+    }
+
+    /// Extend the expiry by the time to live and return the model with a bumped version; the
+    /// value hash is unchanged.  The ttl is added to the current expiry, or to now if the model
+    /// has already expired or doesn't expire.
+    pub fn extend(&self, ttl: Duration) -> Model<T, K> {
+        let now = get_now();
+        let from = match self.expires_at {
+            Some(expires_at) if expires_at > now => expires_at,
+            _ => now,
+        };
+
+        let mut model = self.clone();
+        model.version = self.version.update(self.version.hash);
+        model.expires_at = Some(from + ttl);
+
+        model
+    }
+
+    /// Restore a soft deleted model to the status if the table allows it and clear the deletion
+    /// time.  Returns an error if the model isn't deleted or the table doesn't allow restoring
+    /// to the status, see `Transitions::is_restore_allowed`.
+    pub fn restore_with(
+        &self,
+        to: Status,
+        transitions: &Transitions,
+    ) -> Result<Model<T, K>, TransitionError> {
+        let from = self.status.kind();
+        if from != StatusKind::Deleted || !transitions.is_restore_allowed(to.kind()) {
+            return Err(TransitionError {
+                from,
+                to: to.kind(),
+            });
+        }
+
+        Ok(Model {
+            key: self.key.clone(),
+            version: self.version.update(self.version.hash),
+            schema: self.schema,
+            status: to,
+            value: self.value.clone(),
+            deleted_at: None,
+            tenant: self.tenant.clone(),
+            expires_at: self.expires_at,
+        })
+    }
+}
+
+/// When soft deleted models may be purged, i.e., removed for good.  A model is purgeable once it
+/// has been deleted for longer than the retention period.  Models deleted before `deleted_at`
+/// was tracked fall back to their last update time.
+///
+/// # Example:
+///
+/// ```rust
+/// use chrono::{Duration, Utc};
+/// use domain_keys::models::{Model, PurgePolicy};
+///
+/// let policy = PurgePolicy::new(Duration::days(30));
+/// let deleted = Model::new("my value".to_string()).mark_deleted();
+/// let now = Utc::now().naive_utc();
+///
+/// assert!(!policy.is_purgeable(&deleted, now));
+/// assert!(policy.is_purgeable(&deleted, now + Duration::days(31)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PurgePolicy {
+    pub retention: Duration,
+}
+
+impl PurgePolicy {
+    /// Create a policy with the retention period.
+    pub fn new(retention: Duration) -> PurgePolicy {
+        PurgePolicy { retention }
+    }
+
+    /// Return true if the model is deleted and its retention period has passed.
+    pub fn is_purgeable<T, K>(&self, model: &Model<T, K>, now: TS) -> bool {
+        if !model.is_deleted() {
+            return false;
         }
+
+        let deleted_at = model.deleted_at.unwrap_or(model.version.updated_at);
+
+        now - deleted_at > self.retention
+    }
+}
+
+impl<T: Clone + DomainModel> Model<T, T::Key> {
+    /// Move the model to the new status using the domain's transition table.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::keys::RouteId;
+    /// use domain_keys::models::{DomainModel, Model, ModelError, Status, StatusFlags};
+    ///
+    /// #[derive(Debug, Clone, PartialEq, serde::Serialize)]
+    /// struct Invite(String);
+    ///
+    /// impl DomainModel for Invite {
+    ///     type Key = RouteId;
+    ///
+    ///     fn validate(_: &Model<Self>) -> Vec<ModelError> {
+    ///         vec![]
+    ///     }
+    /// }
+    ///
+    /// let model = Model::new(Invite("sam@rcs.com".to_string()));
+    /// let pending = model.transition(Status::Pending(StatusFlags::NONE)).unwrap();
+    /// assert_eq!(pending.version.update_count, 1);
+    ///
+    /// let deleted = pending.transition(Status::Deleted(StatusFlags::NONE)).unwrap();
+    /// assert!(deleted.transition(Status::Active(StatusFlags::NONE)).is_err());
+    /// ```
+    pub fn transition(&self, to: Status) -> Result<Model<T, T::Key>, TransitionError> {
+        self.transition_with(to, &T::transitions())
+    }
+
+    /// Restore the soft deleted model to the status using the domain's transition table.
+    pub fn restore(&self, to: Status) -> Result<Model<T, T::Key>, TransitionError> {
+        self.restore_with(to, &T::transitions())
     }
 }
 
+impl<T: DomainModel + serde::Serialize> Model<T, T::Key> {
+    /// Create a new model from the value with a new key of the domain's key type, see
+    /// `DomainModel::Key`, at the domain's schema version.
+    pub fn new_domain(value: T) -> Model<T, T::Key> {
+        Model::new_keyed(value).with_schema(T::SCHEMA)
+    }
+
+    /// Return the json for the domain's named view of the model.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::keys::RouteId;
+    /// use domain_keys::models::{DomainModel, Model, ModelError};
+    /// use domain_keys::projection::{Projection, View, LIST};
+    ///
+    /// #[derive(Debug, Clone, serde::Serialize)]
+    /// struct Contact {
+    ///     name: String,
+    ///     phone: String,
+    /// }
+    ///
+    /// impl DomainModel for Contact {
+    ///     type Key = RouteId;
+    ///
+    ///     fn validate(_: &Model<Self>) -> Vec<ModelError> {
+    ///         vec![]
+    ///     }
+    ///
+    ///     fn views() -> Projection {
+    ///         Projection::new().view(View::new(LIST).include(&["/key", "/value"]).mask(&["/value/phone"]))
+    ///     }
+    /// }
+    ///
+    /// let model = Model::new(Contact { name: "sam".to_string(), phone: "123-555-3333".to_string() });
+    /// let list = model.view(LIST).unwrap();
+    ///
+    /// assert_eq!(list["value"]["phone"], "***");
+    /// assert!(model.view("missing").is_err());
+    /// ```
+    pub fn view(&self, name: &str) -> Result<serde_json::Value, ProjectionError> {
+        T::views().project(self, name)
+    }
+}
+
+/// The result of `Model::apply`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Applied<T, K = RouteId> {
+    /// the value hash matches the current version; nothing to write
+    Unchanged,
+    /// the updated model with a bumped version
+    Updated(Model<T, K>),
+}
+
+impl<T, K> Applied<T, K> {
+    /// Return true if the value changed.
+    pub fn is_changed(&self) -> bool {
+        matches!(self, Applied::Updated(_))
+    }
+}
+
+/// Validation errors for a model's value.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ModelError {
     Email(Cow<'static, str>),
+    /// a field error from a [`crate::validation::Validation`]
+    Field(FieldError),
 }
 
-trait DomainModel<T> {
-    fn validate(model: &Model<T>) -> Vec<ModelError>;
-    fn insert(model: Model<T>) -> Result<Model<T>>;
-    fn update(model: Model<T>) -> Result<Model<T>>;
+impl fmt::Display for ModelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModelError::Email(email) => write!(f, "invalid email: {:?}", email),
+            ModelError::Field(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ModelError {}
+
+/// The contract for a domain value type, e.g., a user or an order, stored in a `Model`.
+/// Storage is handled separately by a [`crate::repository::Repository`].
+pub trait DomainModel: Sized {
+    /// the key type for models of this domain, e.g., `RouteId` or a `DomainId`
+    type Key: ModelKey;
 
-    /// this can be used to mask or slim down attribute data
-    fn list_view(model: &Model<T>) -> Model<T>;
+    /// the schema version of the serialized value; bump it when the value changes shape and
+    /// register an upcaster for the old version, see [`crate::schema::Upcasters`]
+    const SCHEMA: u16 = FIRST_SCHEMA;
+
+    /// validate the model and return all errors; an empty list means the model is valid.  Use a
+    /// [`crate::validation::Validation`] and `ValidationReport::into_errors` to build the list.
+    fn validate(model: &Model<Self, Self::Key>) -> Vec<ModelError>;
+
+    /// the named views of this domain, e.g., list, public and admin; see [`crate::projection`]
+    fn views() -> Projection {
+        Projection::new()
+    }
+
+    /// the allowed status transitions for this domain; override to customize
+    fn transitions() -> Transitions {
+        Transitions::default()
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::projection::{View, ADMIN, LIST, PUBLIC};
     use std::borrow::Cow;
     use std::thread;
     use std::time::Duration;
-    // use anyhow::Result;
 
     #[test]
     fn new_version() {
@@ -160,8 +1200,8 @@ mod tests {
         let v2 = v1.update(new_hash);
 
         assert_eq!(
-            v1.created_at.timestamp_micros(),
-            v2.created_at.timestamp_micros()
+            v1.created_at.and_utc().timestamp_micros(),
+            v2.created_at.and_utc().timestamp_micros()
         );
 
         assert_ne!(v2.created_at, v2.updated_at);
@@ -172,6 +1212,27 @@ mod tests {
         assert_eq!(v2.hash, new_hash);
     }
 
+    #[test]
+    fn compare_and_update() {
+        let v1 = Version::new(1);
+
+        let v2 = v1.compare_and_update(0, 2).unwrap();
+        assert_eq!(v2.update_count, 1);
+        assert_eq!(v2.hash, 2);
+        assert_eq!(v2.created_at, v1.created_at);
+
+        let err = v2.compare_and_update(0, 3).unwrap_err();
+        assert_eq!(err.expected, 0);
+        assert_eq!(err.actual, 1);
+        assert_eq!(
+            err.to_string(),
+            "version conflict, expected update count 0 but found 1"
+        );
+
+        let v3 = v2.compare_and_update(1, 3).unwrap();
+        assert_eq!(v3.update_count, 2);
+    }
+
     #[test]
     fn new_model() {
         let value = String::from("my test");
@@ -184,11 +1245,11 @@ mod tests {
 
     #[test]
     fn create_model() {
-        let key = RouteKey::create();
-        let kcopy = String::from(&key);
+        let key = RouteId::generate();
+        let kcopy = key.clone();
         let version = Version::new(10u64);
         let value = "me".to_string();
-        let status = Status::Active(128);
+        let status = Status::Active(StatusFlags::from_bits(128));
 
         let model = Model::create_model(key, &version, &status, &value);
 
@@ -198,6 +1259,428 @@ mod tests {
         assert_eq!(model.value, value);
     }
 
+    #[test]
+    fn stable_hash() {
+        // pinned; the hash must not change across rust releases
+        assert_eq!(Model::calc_hash(&"my test".to_string()), 0xc0cda4f6d9919c79);
+
+        let person = Peep::new(
+            Cow::from("dpw@rcs.com"),
+            Cow::from("steve johnson"),
+            Cow::from("123-555-3333"),
+        );
+        let json = r#"{"email":"dpw@rcs.com","name":"steve johnson","phone":"123-555-3333"}"#;
+        assert_eq!(
+            Model::calc_hash(&person),
+            crate::hash::fnv1a64(json.as_bytes())
+        );
+    }
+
+    #[test]
+    fn apply() {
+        let model = Model::new(10_u64);
+
+        let applied = model.apply(10);
+        assert_eq!(applied, Applied::Unchanged);
+        assert!(!applied.is_changed());
+
+        let applied = model.apply(11);
+        assert!(applied.is_changed());
+        match applied {
+            Applied::Updated(updated) => {
+                assert_eq!(updated.key, model.key);
+                assert_eq!(updated.value, 11);
+                assert_eq!(updated.status, model.status);
+                assert_eq!(updated.version.update_count, 1);
+                assert_eq!(updated.version.hash, Model::calc_hash(&11));
+                assert_eq!(updated.version.created_at, model.version.created_at);
+            }
+            Applied::Unchanged => panic!("should be updated"),
+        }
+    }
+
+    #[test]
+    fn soft_delete_lifecycle() {
+        let model = Model::new("my value".to_string());
+        assert!(!model.is_deleted());
+        let table = Transitions::default();
+        assert!(model
+            .restore_with(Status::Active(StatusFlags::NONE), &table)
+            .is_err());
+
+        let model = model
+            .transition_with(
+                Status::Active(StatusFlags::VERIFIED),
+                &Transitions::default(),
+            )
+            .unwrap();
+        let deleted = model.mark_deleted();
+        assert_eq!(deleted.status, Status::Deleted(StatusFlags::VERIFIED));
+        assert_eq!(deleted.version.hash, model.version.hash);
+        assert_eq!(deleted.mark_deleted(), deleted);
+
+        let err = deleted
+            .restore_with(Status::Deleted(StatusFlags::NONE), &table)
+            .unwrap_err();
+        assert_eq!(err.to, StatusKind::Deleted);
+
+        let restored = deleted
+            .restore_with(Status::Inactive(StatusFlags::NONE), &table)
+            .unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert!(deleted
+            .restore_with(Status::Active(StatusFlags::NONE), &table.without_restore())
+            .is_err());
+
+        // transitions to and from Deleted keep deleted_at in step with the status
+        let transitions = Transitions::default().allow(StatusKind::Deleted, StatusKind::Active);
+        let active = deleted
+            .transition_with(Status::Active(StatusFlags::NONE), &transitions)
+            .unwrap();
+        assert_eq!(active.deleted_at, None);
+        let deleted = active
+            .transition_with(Status::Deleted(StatusFlags::NONE), &transitions)
+            .unwrap();
+        assert!(deleted.deleted_at.is_some());
+    }
+
+    #[test]
+    fn purge_policy() {
+        let policy = PurgePolicy::new(chrono::Duration::hours(1));
+        let model = Model::new("my value".to_string());
+        let now = get_now();
+
+        assert!(!policy.is_purgeable(&model, now + chrono::Duration::days(1)));
+
+        // legacy deleted models without deleted_at use the updated time
+        let mut legacy = model.clone();
+        legacy.status = Status::Deleted(StatusFlags::NONE);
+        assert!(!policy.is_purgeable(&legacy, now));
+        assert!(policy.is_purgeable(&legacy, now + chrono::Duration::hours(2)));
+    }
+
+    #[test]
+    fn deleted_at_serde() {
+        let model = Model::new("my value".to_string());
+        let json = serde_json::to_string(&model).unwrap();
+        assert!(!json.contains("deleted_at"));
+        assert_eq!(serde_json::from_str::<Model<String>>(&json).unwrap(), model);
+
+        let deleted = model.mark_deleted();
+        let json = serde_json::to_string(&deleted).unwrap();
+        assert!(json.contains("deleted_at"));
+        assert_eq!(
+            serde_json::from_str::<Model<String>>(&json).unwrap(),
+            deleted
+        );
+    }
+
+    // none of these are Ord, Hash or Default
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Reading {
+        celsius: f64,
+        labels: std::collections::HashMap<String, String>,
+        raw: serde_json::Value,
+    }
+
+    #[test]
+    fn relaxed_bounds() {
+        let mut labels = std::collections::HashMap::new();
+        labels.insert("site".to_string(), "sea".to_string());
+        labels.insert("unit".to_string(), "c".to_string());
+        let reading = Reading {
+            celsius: 21.5,
+            labels,
+            raw: serde_json::json!({"b": [1, 2], "a": null}),
+        };
+
+        let model = Model::new(reading.clone());
+        assert!(model.rehash().is_none());
+
+        // the hash is of the canonical json, so map order doesn't matter
+        let mut reordered = reading.clone();
+        let mut pairs: Vec<(String, String)> = reading.labels.clone().into_iter().collect();
+        pairs.reverse();
+        reordered.labels = pairs.into_iter().collect();
+        assert_eq!(model.apply(reordered), Applied::Unchanged);
+
+        let mut warmer = reading;
+        warmer.celsius = 22.0;
+        match model.apply(warmer) {
+            Applied::Updated(updated) => assert_eq!(updated.version.update_count, 1),
+            Applied::Unchanged => panic!("value should have changed"),
+        }
+
+        let json = serde_json::to_string(&model).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Model<Reading>>(&json).unwrap(),
+            model
+        );
+    }
+
+    #[test]
+    fn expiry() {
+        let model = Model::new("my value".to_string());
+        let now = Utc::now().naive_utc();
+        assert!(!model.is_expired(now));
+        assert_eq!(model.ttl(now), None);
+        assert!(!serde_json::to_string(&model)
+            .unwrap()
+            .contains("expires_at"));
+
+        let mut expired = model.clone();
+        expired.expires_at = Some(now - chrono::Duration::minutes(1));
+        assert!(expired.is_expired(now));
+        assert_eq!(expired.ttl(now), Some(chrono::Duration::zero()));
+
+        // extending an expired model starts from now
+        let extended = expired.extend(chrono::Duration::minutes(10));
+        assert!(!extended.is_expired(now + chrono::Duration::minutes(9)));
+        assert!(extended.is_expired(now + chrono::Duration::minutes(11)));
+        assert_eq!(extended.version.hash, model.version.hash);
+
+        let json = serde_json::to_string(&extended).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Model<String>>(&json).unwrap(),
+            extended
+        );
+    }
+
+    #[test]
+    fn tenant_serde() {
+        let model = Model::new("my value".to_string());
+        assert!(!serde_json::to_string(&model).unwrap().contains("tenant"));
+
+        let tenant = crate::tenant::TenantId::new("acme").unwrap();
+        let owned = model.with_tenant(tenant.clone());
+        let json = serde_json::to_string(&owned).unwrap();
+        assert!(json.contains(r#""tenant":"acme""#));
+        assert_eq!(serde_json::from_str::<Model<String>>(&json).unwrap(), owned);
+
+        // transitions and updates keep the owner
+        let deleted = owned.mark_deleted();
+        assert!(deleted.belongs_to(Some(&tenant)));
+        match deleted.apply("new value".to_string()) {
+            Applied::Updated(updated) => assert_eq!(updated.tenant, Some(tenant)),
+            Applied::Unchanged => panic!("value should have changed"),
+        }
+
+        let bad = json.replace(r#""acme""#, r#""acme:corp""#);
+        assert!(serde_json::from_str::<Model<String>>(&bad).is_err());
+    }
+
+    #[test]
+    fn schema_serde() {
+        let model = Model::new("my value".to_string());
+        assert_eq!(model.schema, FIRST_SCHEMA);
+
+        let model = model.with_schema(3);
+        let json = serde_json::to_string(&model).unwrap();
+        assert!(json.contains(r#""schema":3"#));
+        assert_eq!(serde_json::from_str::<Model<String>>(&json).unwrap(), model);
+
+        // updates keep the schema version
+        let deleted = model.mark_deleted();
+        assert_eq!(deleted.schema, 3);
+        match deleted.apply("new value".to_string()) {
+            Applied::Updated(updated) => assert_eq!(updated.schema, 3),
+            Applied::Unchanged => panic!("value should have changed"),
+        }
+
+        // models stored before the schema was tracked are version 1
+        let legacy = json.replace(r#""schema":3,"#, "");
+        assert!(!legacy.contains("schema"));
+        let legacy: Model<String> = serde_json::from_str(&legacy).unwrap();
+        assert_eq!(legacy.schema, FIRST_SCHEMA);
+    }
+
+    #[test]
+    fn status_flags() {
+        let flags = StatusFlags::FLAGGED | StatusFlags::LOCKED;
+        assert!(flags.contains(StatusFlags::FLAGGED));
+        assert!(!flags.contains(StatusFlags::VERIFIED));
+        assert_eq!(flags.without(StatusFlags::FLAGGED), StatusFlags::LOCKED);
+        assert!(StatusFlags::NONE.is_empty());
+
+        assert_eq!(StatusFlags::NONE.to_string(), "none");
+        assert_eq!(StatusFlags::from_bits(0x81).to_string(), "flagged|domain3");
+
+        let status = Status::Blocked(StatusFlags::ESCALATED);
+        assert_eq!(status.flags(), StatusFlags::ESCALATED);
+        assert_eq!(
+            status.with_flags(StatusFlags::NONE),
+            Status::Blocked(StatusFlags::NONE)
+        );
+    }
+
+    #[test]
+    fn status_serde_compatible() {
+        // stored before flags replaced the raw u8
+        let stored = r#"{"t":"Active","c":128}"#;
+
+        let status: Status = serde_json::from_str(stored).unwrap();
+        assert_eq!(status, Status::Active(StatusFlags::from_bits(128)));
+        assert_eq!(status.flags(), StatusFlags::domain(3));
+        assert_eq!(serde_json::to_string(&status).unwrap(), stored);
+
+        for kind in StatusKind::ALL {
+            let status = Status::new(kind, StatusFlags::VERIFIED);
+            let json = serde_json::to_string(&status).unwrap();
+            assert_eq!(json, format!(r#"{{"t":"{:?}","c":2}}"#, kind));
+            assert_eq!(serde_json::from_str::<Status>(&json).unwrap(), status);
+        }
+    }
+
+    #[test]
+    fn default_transitions() {
+        use StatusKind::*;
+
+        let table = Transitions::default();
+        let allowed = [
+            (New, Pending),
+            (New, Active),
+            (Pending, Active),
+            (Active, Inactive),
+            (Inactive, Active),
+            (Active, Blocked),
+            (Blocked, Active),
+            (Active, Active),
+            (Processed, Deleted),
+        ];
+        for (from, to) in allowed {
+            assert!(table.is_allowed(from, to), "{:?} -> {:?}", from, to);
+        }
+
+        for from in StatusKind::ALL {
+            assert!(table.is_allowed(from, Deleted));
+        }
+
+        for to in StatusKind::ALL {
+            assert_eq!(table.is_allowed(Deleted, to), to == Deleted);
+        }
+
+        assert!(!table.is_allowed(Inactive, New));
+        assert!(!table.is_allowed(Processed, Active));
+    }
+
+    #[test]
+    fn transition() {
+        let model = Model::new(10_u64);
+
+        let active = model
+            .transition_with(
+                Status::Active(StatusFlags::FLAGGED),
+                &Transitions::default(),
+            )
+            .unwrap();
+        assert_eq!(active.status, Status::Active(StatusFlags::FLAGGED));
+        assert_eq!(active.version.update_count, 1);
+        assert_eq!(active.version.hash, model.version.hash);
+
+        let err = active
+            .transition_with(Status::New(StatusFlags::NONE), &Transitions::default())
+            .unwrap_err();
+        assert_eq!(
+            err,
+            TransitionError {
+                from: StatusKind::Active,
+                to: StatusKind::New
+            }
+        );
+        assert_eq!(err.to_string(), "illegal status transition: Active -> New");
+
+        // customize per domain
+        let custom = Transitions::default().deny(StatusKind::New, StatusKind::Active);
+        assert!(model
+            .transition_with(Status::Active(StatusFlags::NONE), &custom)
+            .is_err());
+    }
+
+    #[test]
+    fn peep_transitions() {
+        let peep = Peep::new(Cow::from("a@b.com"), Cow::from("a"), Cow::from("1"));
+        let model = Model::new(peep);
+
+        // peeps skip pending
+        assert!(model
+            .transition(Status::Pending(StatusFlags::NONE))
+            .is_err());
+        assert!(model.transition(Status::Active(StatusFlags::NONE)).is_ok());
+
+        let deleted = model.mark_deleted();
+        assert!(deleted.restore(Status::Active(StatusFlags::NONE)).is_ok());
+    }
+
+    #[test]
+    fn final_deletes() {
+        #[derive(Debug, Clone, PartialEq, Serialize)]
+        struct Entry(u64);
+
+        impl DomainModel for Entry {
+            type Key = RouteId;
+
+            fn validate(_: &Model<Entry>) -> Vec<ModelError> {
+                vec![]
+            }
+
+            fn transitions() -> Transitions {
+                Transitions::default().without_restore()
+            }
+        }
+
+        let deleted = Model::new_domain(Entry(10)).mark_deleted();
+
+        for to in [
+            Status::New(StatusFlags::NONE),
+            Status::Active(StatusFlags::NONE),
+            Status::Inactive(StatusFlags::NONE),
+        ] {
+            let err = deleted.restore(to.clone()).unwrap_err();
+            assert_eq!(err.from, StatusKind::Deleted);
+            assert_eq!(err.to, to.kind());
+            assert!(deleted.transition(to).is_err());
+        }
+    }
+
+    #[test]
+    fn default_models() {
+        use crate::keys::{DomainId, KeyDomain, ModelKey, TimeStampId};
+
+        struct User;
+        impl KeyDomain for User {
+            const PREFIX: &'static str = "usr";
+        }
+
+        let model: Model<u64, DomainId<User>> = Model::default();
+        assert!(DomainId::<User>::parse(&model.key).is_ok());
+        assert_eq!(model.status, Status::New(StatusFlags::NONE));
+        assert_eq!(model.deleted_at, None);
+
+        let model: Model<String, TimeStampId> = Model {
+            value: "my value".to_string(),
+            ..Model::default()
+        };
+        assert!(TimeStampId::parse(&model.key).is_ok());
+        assert_eq!(model.schema, FIRST_SCHEMA);
+
+        // every default gets its own key
+        assert_ne!(Model::<u64>::default().key, Model::<u64>::default().key);
+    }
+
+    #[test]
+    fn rehash() {
+        let model = Model::new(10_u64);
+        assert!(model.rehash().is_none());
+
+        let mut legacy = model.clone();
+        legacy.version.hash = 5;
+
+        let migrated = legacy.rehash().unwrap();
+        assert_eq!(migrated.version.hash, model.version.hash);
+        assert_eq!(migrated.version.update_count, 0);
+        assert_eq!(migrated.version.updated_at, legacy.version.updated_at);
+    }
+
     #[test]
     fn create_person_model() {
         let email = Cow::from("dpw@rcs.com");
@@ -205,10 +1688,10 @@ mod tests {
         let phone = Cow::from("123-555-3333");
         let person = Peep::new(email, name, phone);
 
-        let key = RouteKey::create();
-        let kcopy = String::from(&key);
+        let key = RouteId::generate();
+        let kcopy = key.clone();
         let version = Version::new(10u64);
-        let status = Status::Active(128);
+        let status = Status::Active(StatusFlags::from_bits(128));
 
         let model = Model::create_model(key, &version, &status, &person);
 
@@ -225,12 +1708,12 @@ mod tests {
         let phone = Cow::from("123-555-3333");
         let person = Peep::new(email, name, phone);
 
-        let key = RouteKey::create();
-        let kcopy = String::from(&key);
+        let key = RouteId::generate();
+        let kcopy = key.clone();
         let version = Version::new(10u64);
         assert_eq!(version.update_count, 0);
         let update_count = version.update_count;
-        let status = Status::Active(128);
+        let status = Status::Active(StatusFlags::from_bits(128));
 
         let model = Model::create_model(key, &version, &status, &person);
         assert_eq!(model.version.update_count, 0);
@@ -247,6 +1730,46 @@ mod tests {
         assert_eq!(mcopy.version.update_count, update_count + 1);
     }
 
+    #[test]
+    fn validate_peep() {
+        let peep = Peep::new(Cow::from(""), Cow::from("sam"), Cow::from("123-555-3333"));
+        let model = Model::new(peep);
+
+        let errors = Peep::validate(&model);
+        assert_eq!(errors, vec![ModelError::Email(Cow::from(""))]);
+        assert_eq!(errors[0].to_string(), "invalid email: \"\"");
+
+        let model = Model::new(Peep::new(
+            Cow::from("sam@rcs.com"),
+            Cow::from("sam"),
+            Cow::from("123-555-3333"),
+        ));
+        assert!(Peep::validate(&model).is_empty());
+    }
+
+    #[test]
+    fn declared_views() {
+        let model = Model::new(Peep::new(
+            Cow::from("sam@rcs.com"),
+            Cow::from("sam"),
+            Cow::from("123-555-3333"),
+        ));
+
+        let list = model.view(LIST).unwrap();
+        assert_eq!(list["key"], model.key.as_ref());
+        assert_eq!(list["value"]["name"], "sam");
+        assert_eq!(list["value"]["phone"], "***");
+        assert!(list.get("version").is_none());
+
+        let admin = model.view(ADMIN).unwrap();
+        assert_eq!(admin, serde_json::to_value(&model).unwrap());
+
+        assert_eq!(
+            model.view(PUBLIC),
+            Err(ProjectionError::UnknownView(PUBLIC.to_string()))
+        );
+    }
+
     #[derive(
         Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
     )]
@@ -266,7 +1789,9 @@ mod tests {
         }
     }
 
-    impl DomainModel<Peep> for Peep {
+    impl DomainModel for Peep {
+        type Key = RouteId;
+
         fn validate(model: &Model<Peep>) -> Vec<ModelError> {
             let peep = model.value.clone();
             let mut errors: Vec<ModelError> = Vec::new();
@@ -279,19 +1804,18 @@ mod tests {
             errors
         }
 
-        fn insert(model: Model<Peep>) -> Result<Model<Peep>> {
-            Ok(model)
+        fn transitions() -> Transitions {
+            Transitions::default().deny(StatusKind::New, StatusKind::Pending)
         }
 
-        fn update(model: Model<Peep>) -> Result<Model<Peep>> {
-            Ok(model)
-        }
-
-        fn list_view(model: &Model<Peep>) -> Model<Peep> {
-            let mut value = model.value.clone();
-            value.phone = Cow::from("");
-
-            Model::create_model(model.key.to_string(), &model.version, &model.status, &value)
+        fn views() -> Projection {
+            Projection::new()
+                .view(
+                    View::new(LIST)
+                        .include(&["/key", "/status", "/value"])
+                        .mask(&["/value/phone"]),
+                )
+                .view(View::new(ADMIN))
         }
     }
 }
@@ -0,0 +1,687 @@
+//!
+//! Field level diff and patch for models with [RFC 6902](https://www.rfc-editor.org/rfc/rfc6902)
+//! JSON Patch.  Paths are json pointers into the serialized model, e.g., `/value/name` or
+//! `/status`, so a patch can change the value and status of a model.
+//!
+//! The model key, version, schema, tenant, deletion and expiry times are owned by the model and
+//! repository, so `diff` never includes them and `apply` rejects any patch that touches them.
+//! A status change must be allowed by the transition table, and moving to or from `Deleted`
+//! sets or clears the deletion time like `Model::transition_with`; restore a deleted model with
+//! `Model::restore`.
+//!
+
+use crate::history::{field_changes, FieldChange};
+use crate::keys::ModelKey;
+use crate::models::{Model, TransitionError, Transitions};
+use serde::de::DeserializeOwned;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+
+/// A JSON Patch operation.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOp {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl PatchOp {
+    /// Return the target path.
+    pub fn path(&self) -> &str {
+        match self {
+            PatchOp::Add { path, .. }
+            | PatchOp::Remove { path }
+            | PatchOp::Replace { path, .. }
+            | PatchOp::Move { path, .. }
+            | PatchOp::Copy { path, .. }
+            | PatchOp::Test { path, .. } => path,
+        }
+    }
+
+    /// Return the source path for move and copy.
+    pub fn from(&self) -> Option<&str> {
+        match self {
+            PatchOp::Move { from, .. } | PatchOp::Copy { from, .. } => Some(from),
+            _ => None,
+        }
+    }
+}
+
+/// A JSON Patch document; serializes as the json array of operations.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Patch(pub Vec<PatchOp>);
+
+impl Patch {
+    /// Return true if the patch has no operations.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Return the number of operations.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+/// Patch errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatchError {
+    /// the patch touches the model key, version, schema, tenant, deletion or expiry time
+    Forbidden(String),
+    /// the path is not a valid json pointer
+    InvalidPath(String),
+    /// nothing exists at the path
+    NotFound(String),
+    /// a test operation failed
+    TestFailed(String),
+    /// the patched status isn't allowed by the transition table
+    Transition(TransitionError),
+    /// the model could not be serialized, or the patched json is not a valid model
+    Json(String),
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::Forbidden(path) => write!(f, "patch may not change path: {}", path),
+            PatchError::InvalidPath(path) => write!(f, "invalid json pointer: {}", path),
+            PatchError::NotFound(path) => write!(f, "path not found: {}", path),
+            PatchError::TestFailed(path) => write!(f, "test failed for path: {}", path),
+            PatchError::Transition(err) => write!(f, "{}", err),
+            PatchError::Json(msg) => write!(f, "json error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+impl From<TransitionError> for PatchError {
+    fn from(err: TransitionError) -> Self {
+        PatchError::Transition(err)
+    }
+}
+
+impl From<serde_json::Error> for PatchError {
+    fn from(err: serde_json::Error) -> Self {
+        PatchError::Json(err.to_string())
+    }
+}
+
+// the model fields a patch may not touch
+const PROTECTED: [&str; 6] = [
+    "/key",
+    "/version",
+    "/schema",
+    "/tenant",
+    "/deleted_at",
+    "/expires_at",
+];
+
+/// Return the patch that changes the old model into the new model; the protected fields, i.e.,
+/// key, version, schema, tenant, deletion and expiry times, are not compared.  Objects are compared field by field; array elements are replaced by index
+/// and extra elements are added or removed at the end.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::models::{Applied, Model};
+/// use domain_keys::patch::{diff, PatchOp};
+/// use serde_json::json;
+///
+/// let model = Model::new(vec!["a".to_string()]);
+/// let updated = match model.apply(vec!["b".to_string()]) {
+///     Applied::Updated(updated) => updated,
+///     Applied::Unchanged => panic!("value should have changed"),
+/// };
+///
+/// let patch = diff(&model, &updated).unwrap();
+///
+/// assert_eq!(
+///     patch.0,
+///     vec![PatchOp::Replace { path: "/value/0".to_string(), value: json!("b") }]
+/// );
+/// ```
+pub fn diff<T, K>(old: &Model<T, K>, new: &Model<T, K>) -> Result<Patch, PatchError>
+where
+    T: serde::Serialize,
+    K: serde::Serialize,
+{
+    let mut old = serde_json::to_value(old)?;
+    let mut new = serde_json::to_value(new)?;
+
+    for doc in [&mut old, &mut new] {
+        if let Value::Object(map) = doc {
+            for path in PROTECTED {
+                map.remove(&path[1..]);
+            }
+        }
+    }
+
+    // the same walk as the history changes, so the two diffs agree on arrays and nulls
+    let ops = field_changes(&old, &new)
+        .into_iter()
+        .map(|change| match change {
+            FieldChange {
+                path,
+                old: None,
+                new: Some(value),
+            } => PatchOp::Add { path, value },
+            FieldChange {
+                path, new: None, ..
+            } => PatchOp::Remove { path },
+            FieldChange {
+                path,
+                new: Some(value),
+                ..
+            } => PatchOp::Replace { path, value },
+        })
+        .collect();
+
+    Ok(Patch(ops))
+}
+
+/// Apply the patch to the model with the default transition table, see `apply_with`.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::models::{Model, Status, StatusFlags};
+/// use domain_keys::patch::{apply, Patch, PatchError};
+///
+/// let model = Model::new("my value".to_string());
+///
+/// let patch: Patch = serde_json::from_str(r#"[
+///     {"op": "test", "path": "/value", "value": "my value"},
+///     {"op": "replace", "path": "/value", "value": "new value"},
+///     {"op": "replace", "path": "/status/t", "value": "Active"}
+/// ]"#).unwrap();
+///
+/// let patched = apply(&model, &patch).unwrap();
+/// assert_eq!(patched.key, model.key);
+/// assert_eq!(patched.value, "new value");
+/// assert_eq!(patched.status, Status::Active(StatusFlags::NONE));
+/// assert_eq!(patched.version.update_count, 1);
+///
+/// let patch: Patch = serde_json::from_str(r#"[
+///     {"op": "replace", "path": "/version/update_count", "value": 99}
+/// ]"#).unwrap();
+///
+/// assert_eq!(
+///     apply(&model, &patch),
+///     Err(PatchError::Forbidden("/version/update_count".to_string()))
+/// );
+/// ```
+pub fn apply<T, K>(model: &Model<T, K>, patch: &Patch) -> Result<Model<T, K>, PatchError>
+where
+    T: serde::Serialize + DeserializeOwned,
+    K: ModelKey,
+{
+    apply_with(model, patch, &Transitions::default())
+}
+
+/// Apply the patch to the model and return the patched model with the same protected fields and
+/// a bumped version, or the model's version if neither the value nor the status changed.  A
+/// status change must be allowed by the transitions, e.g., a domain's `DomainModel::transitions`.
+/// The patch is applied to a copy, so nothing changes if any operation fails.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::models::{Model, Status, StatusFlags, StatusKind, Transitions};
+/// use domain_keys::patch::{apply_with, Patch, PatchError};
+///
+/// let model = Model::new("my value".to_string());
+/// let patch: Patch = serde_json::from_str(r#"[
+///     {"op": "replace", "path": "/status/t", "value": "Deleted"}
+/// ]"#).unwrap();
+///
+/// let deleted = apply_with(&model, &patch, &Transitions::default()).unwrap();
+/// assert!(deleted.is_deleted());
+/// assert!(deleted.deleted_at.is_some());
+///
+/// let frozen = Transitions::new();
+/// assert!(matches!(
+///     apply_with(&model, &patch, &frozen),
+///     Err(PatchError::Transition(_))
+/// ));
+///
+/// // a patch that changes nothing doesn't bump the version
+/// let unchanged = apply_with(&model, &Patch::default(), &frozen).unwrap();
+/// assert_eq!(unchanged.version, model.version);
+/// ```
+pub fn apply_with<T, K>(
+    model: &Model<T, K>,
+    patch: &Patch,
+    transitions: &Transitions,
+) -> Result<Model<T, K>, PatchError>
+where
+    T: serde::Serialize + DeserializeOwned,
+    K: ModelKey,
+{
+    for op in &patch.0 {
+        check_path(op.path())?;
+        if let Some(from) = op.from() {
+            check_path(from)?;
+        }
+    }
+
+    let mut doc = serde_json::to_value(model)?;
+    for op in &patch.0 {
+        apply_op(&mut doc, op)?;
+    }
+
+    let mut patched: Model<T, K> = serde_json::from_value(doc)?;
+    patched.key = model.key.clone();
+    patched.schema = model.schema;
+    patched.tenant = model.tenant.clone();
+    patched.expires_at = model.expires_at;
+
+    let status_changed = patched.status != model.status;
+    patched.deleted_at = if status_changed {
+        model.transition_deleted_at(&patched.status, transitions)?
+    } else {
+        model.deleted_at
+    };
+
+    let hash = Model::try_calc_hash(&patched.value)?;
+    patched.version = if status_changed || hash != model.version.hash {
+        model.version.update(hash)
+    } else {
+        model.version.clone()
+    };
+
+    Ok(patched)
+}
+
+// reject the root and anything in or under a protected field
+fn check_path(path: &str) -> Result<(), PatchError> {
+    let protected = path.is_empty()
+        || PROTECTED
+            .iter()
+            .any(|p| path == *p || path.starts_with(&format!("{}/", p)));
+
+    if protected {
+        Err(PatchError::Forbidden(path.to_string()))
+    } else {
+        Ok(())
+    }
+}
+
+fn apply_op(doc: &mut Value, op: &PatchOp) -> Result<(), PatchError> {
+    match op {
+        PatchOp::Add { path, value } => add(doc, path, value.clone()),
+        PatchOp::Remove { path } => remove(doc, path).map(|_| ()),
+        PatchOp::Replace { path, value } => {
+            let target = doc
+                .pointer_mut(path)
+                .ok_or_else(|| PatchError::NotFound(path.clone()))?;
+            *target = value.clone();
+            Ok(())
+        }
+        PatchOp::Move { from, path } => {
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(PatchError::InvalidPath(path.clone()));
+            }
+            let value = remove(doc, from)?;
+            add(doc, path, value)
+        }
+        PatchOp::Copy { from, path } => {
+            let value = doc
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| PatchError::NotFound(from.clone()))?;
+            add(doc, path, value)
+        }
+        PatchOp::Test { path, value } => match doc.pointer(path) {
+            Some(current) if current == value => Ok(()),
+            Some(_) => Err(PatchError::TestFailed(path.clone())),
+            None => Err(PatchError::NotFound(path.clone())),
+        },
+    }
+}
+
+// split the pointer into the parent pointer and the unescaped last token
+fn split_pointer(path: &str) -> Result<(&str, String), PatchError> {
+    match path.rfind('/') {
+        Some(idx) => {
+            let token = path[idx + 1..].replace("~1", "/").replace("~0", "~");
+            Ok((&path[..idx], token))
+        }
+        None => Err(PatchError::InvalidPath(path.to_string())),
+    }
+}
+
+fn parent_mut<'a>(doc: &'a mut Value, path: &str) -> Result<(&'a mut Value, String), PatchError> {
+    let (parent, token) = split_pointer(path)?;
+    let parent = doc
+        .pointer_mut(parent)
+        .ok_or_else(|| PatchError::NotFound(path.to_string()))?;
+
+    Ok((parent, token))
+}
+
+fn array_index(token: &str, len: usize, path: &str) -> Result<usize, PatchError> {
+    let valid = !token.is_empty() && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(idx) if valid && idx <= len => Ok(idx),
+        Ok(_) if valid => Err(PatchError::NotFound(path.to_string())),
+        _ => Err(PatchError::InvalidPath(path.to_string())),
+    }
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> Result<(), PatchError> {
+    let (parent, token) = parent_mut(doc, path)?;
+    match parent {
+        Value::Object(map) => {
+            map.insert(token, value);
+            Ok(())
+        }
+        Value::Array(list) if token == "-" => {
+            list.push(value);
+            Ok(())
+        }
+        Value::Array(list) => {
+            let idx = array_index(&token, list.len(), path)?;
+            list.insert(idx, value);
+            Ok(())
+        }
+        _ => Err(PatchError::NotFound(path.to_string())),
+    }
+}
+
+fn remove(doc: &mut Value, path: &str) -> Result<Value, PatchError> {
+    let (parent, token) = parent_mut(doc, path)?;
+    let removed = match parent {
+        Value::Object(map) => map.remove(&token),
+        Value::Array(list) => {
+            let idx = array_index(&token, list.len(), path)?;
+            if idx < list.len() {
+                Some(list.remove(idx))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+
+    removed.ok_or_else(|| PatchError::NotFound(path.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Applied, Status, StatusFlags, StatusKind};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn updated<T: serde::Serialize>(model: &Model<T>, value: T) -> Model<T> {
+        match model.apply(value) {
+            Applied::Updated(updated) => updated,
+            Applied::Unchanged => panic!("value should have changed"),
+        }
+    }
+
+    fn map(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_round_trip() {
+        let model = Model::new(map(&[("name", "sam"), ("city", "seattle"), ("a/b", "x")]));
+        let mut new = updated(&model, map(&[("name", "kim"), ("zip", "98101")]));
+        new.status = Status::Active(StatusFlags::VERIFIED);
+
+        let patch = diff(&model, &new).unwrap();
+        assert!(patch.0.iter().all(|op| check_path(op.path()).is_ok()));
+        assert!(patch.0.contains(&PatchOp::Remove {
+            path: "/value/a~1b".to_string()
+        }));
+
+        let patched = apply(&model, &patch).unwrap();
+        assert_eq!(patched.value, new.value);
+        assert_eq!(patched.status, new.status);
+        assert_eq!(patched.version.update_count, 1);
+        assert_eq!(patched.version.hash, new.version.hash);
+
+        assert!(diff(&new, &patched).unwrap().is_empty());
+    }
+
+    #[test]
+    fn diff_arrays() {
+        let model = Model::new(vec![1_u8, 2, 3]);
+        let shorter = updated(&model, vec![1, 5]);
+        let longer = updated(&model, vec![1, 2, 3, 4, 5]);
+
+        let patch = diff(&model, &shorter).unwrap();
+        assert_eq!(patch.len(), 2);
+        assert_eq!(apply(&model, &patch).unwrap().value, vec![1, 5]);
+
+        let patch = diff(&model, &longer).unwrap();
+        assert_eq!(patch.len(), 2);
+        assert_eq!(apply(&model, &patch).unwrap().value, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn diff_matches_field_changes() {
+        let model = Model::new(json!({"tags": ["a", "b", "c"], "note": null, "cards": [{"n": 1}]}));
+        let new = updated(
+            &model,
+            json!({"tags": ["x"], "note": "hi", "cards": [{"n": 2}, null], "gone": null}),
+        );
+
+        let patch = diff(&model, &new).unwrap();
+        let changes = field_changes(
+            &json!({ "value": model.value }),
+            &json!({ "value": new.value }),
+        );
+        let paths: Vec<&str> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(
+            patch.0.iter().map(|op| op.path()).collect::<Vec<_>>(),
+            paths
+        );
+
+        assert!(patch.0.contains(&PatchOp::Add {
+            path: "/value/gone".to_string(),
+            value: Value::Null
+        }));
+        assert_eq!(apply(&model, &patch).unwrap().value, new.value);
+    }
+
+    #[test]
+    fn protected_paths() {
+        let model = Model::new("my value".to_string());
+
+        for path in [
+            "",
+            "/key",
+            "/version",
+            "/version/hash",
+            "/schema",
+            "/tenant",
+            "/deleted_at",
+            "/expires_at",
+        ] {
+            let patch = Patch(vec![PatchOp::Remove {
+                path: path.to_string(),
+            }]);
+            assert_eq!(
+                apply(&model, &patch),
+                Err(PatchError::Forbidden(path.to_string()))
+            );
+        }
+
+        let patch = Patch(vec![PatchOp::Copy {
+            from: "/key".to_string(),
+            path: "/value".to_string(),
+        }]);
+        assert_eq!(
+            apply(&model, &patch),
+            Err(PatchError::Forbidden("/key".to_string()))
+        );
+
+        // a similar name is not protected
+        assert!(check_path("/keys").is_ok());
+    }
+
+    #[test]
+    fn status_transitions() {
+        let model = Model::new("my value".to_string());
+        let status = |kind: &str| {
+            Patch(vec![PatchOp::Replace {
+                path: "/status/t".to_string(),
+                value: json!(kind),
+            }])
+        };
+
+        // moving to deleted sets the deletion time like transition_with
+        let deleted = apply(&model, &status("Deleted")).unwrap();
+        assert!(deleted.is_deleted());
+        assert!(deleted.deleted_at.is_some());
+        assert_eq!(deleted.version.update_count, 1);
+
+        // a deleted model is restored with Model::restore, not a patch
+        assert_eq!(
+            apply(&deleted, &status("Active")),
+            Err(PatchError::Transition(TransitionError {
+                from: StatusKind::Deleted,
+                to: StatusKind::Active
+            }))
+        );
+
+        // the deletion time can't be forged or cleared
+        let patch = Patch(vec![PatchOp::Remove {
+            path: "/deleted_at".to_string(),
+        }]);
+        assert_eq!(
+            apply(&deleted, &patch),
+            Err(PatchError::Forbidden("/deleted_at".to_string()))
+        );
+
+        // domains pass their own table
+        let transitions = Transitions::default().deny(StatusKind::New, StatusKind::Active);
+        let err = apply_with(&model, &status("Active"), &transitions).unwrap_err();
+        assert_eq!(err.to_string(), "illegal status transition: New -> Active");
+
+        // flag changes within a status are allowed
+        let patch = Patch(vec![PatchOp::Replace {
+            path: "/status/c".to_string(),
+            value: json!(StatusFlags::LOCKED),
+        }]);
+        let locked = apply_with(&model, &patch, &Transitions::new()).unwrap();
+        assert_eq!(locked.status, Status::New(StatusFlags::LOCKED));
+        assert_eq!(locked.deleted_at, None);
+    }
+
+    #[test]
+    fn no_op_patches() {
+        let model = Model::new(map(&[("name", "sam")]));
+
+        assert_eq!(apply(&model, &Patch::default()).unwrap(), model);
+
+        let patch: Patch = serde_json::from_value(json!([
+            {"op": "test", "path": "/value/name", "value": "sam"},
+            {"op": "replace", "path": "/value/name", "value": "sam"},
+            {"op": "replace", "path": "/status/t", "value": "New"}
+        ]))
+        .unwrap();
+        let patched = apply(&model, &patch).unwrap();
+        assert_eq!(patched.version, model.version);
+        assert_eq!(patched, model);
+
+        // a diff of identical models applies as a no-op
+        let patch = diff(&model, &model).unwrap();
+        assert_eq!(apply(&model, &patch).unwrap().version.update_count, 0);
+    }
+
+    #[test]
+    fn move_copy_test() {
+        let model = Model::new(map(&[("a", "1"), ("b", "2")]));
+
+        let patch: Patch = serde_json::from_value(json!([
+            {"op": "move", "from": "/value/a", "path": "/value/c"},
+            {"op": "copy", "from": "/value/b", "path": "/value/d"},
+            {"op": "test", "path": "/value/c", "value": "1"}
+        ]))
+        .unwrap();
+
+        let patched = apply(&model, &patch).unwrap();
+        assert_eq!(patched.value, map(&[("b", "2"), ("c", "1"), ("d", "2")]));
+
+        let patch = Patch(vec![PatchOp::Test {
+            path: "/value/a".to_string(),
+            value: json!("2"),
+        }]);
+        assert_eq!(
+            apply(&model, &patch),
+            Err(PatchError::TestFailed("/value/a".to_string()))
+        );
+    }
+
+    #[test]
+    fn patch_errors() {
+        let model = Model::new(vec![1_u8]);
+
+        let ops = [
+            (
+                PatchOp::Remove {
+                    path: "/value/3".to_string(),
+                },
+                PatchError::NotFound("/value/3".to_string()),
+            ),
+            (
+                PatchOp::Add {
+                    path: "/value/01".to_string(),
+                    value: json!(2),
+                },
+                PatchError::InvalidPath("/value/01".to_string()),
+            ),
+            (
+                PatchOp::Replace {
+                    path: "/missing".to_string(),
+                    value: json!(2),
+                },
+                PatchError::NotFound("/missing".to_string()),
+            ),
+            (
+                PatchOp::Replace {
+                    path: "value".to_string(),
+                    value: json!(2),
+                },
+                PatchError::NotFound("value".to_string()),
+            ),
+        ];
+
+        for (op, err) in ops {
+            assert_eq!(apply(&model, &Patch(vec![op])), Err(err));
+        }
+
+        // the patched json must still be a model
+        let patch = Patch(vec![PatchOp::Replace {
+            path: "/value".to_string(),
+            value: json!("not a list"),
+        }]);
+        assert!(matches!(apply(&model, &patch), Err(PatchError::Json(_))));
+    }
+
+    #[test]
+    fn serde_format() {
+        let patch = Patch(vec![PatchOp::Move {
+            from: "/value/a".to_string(),
+            path: "/value/b".to_string(),
+        }]);
+
+        assert_eq!(
+            serde_json::to_string(&patch).unwrap(),
+            r#"[{"op":"move","from":"/value/a","path":"/value/b"}]"#
+        );
+    }
+}
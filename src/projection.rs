@@ -0,0 +1,362 @@
+//!
+//! Declarative views of models.  A domain declares named `View`s, e.g., list, public and admin,
+//! each listing the fields to include and the fields to mask.  Views are applied through serde
+//! to any `Model<T>` and return json, so there's no hand-written masked clone per domain.
+//!
+//! Field paths are json pointers into the serialized model, e.g., `/key`, `/status` or
+//! `/value/phone`, the same form used by [`crate::patch`].
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::models::Model;
+//! use domain_keys::projection::{Projection, View};
+//! use std::collections::BTreeMap;
+//!
+//! let views = Projection::new()
+//!     .view(View::new("list").include(&["/key", "/value/name", "/value/phone"]).mask(&["/value/phone"]))
+//!     .view(View::new("admin"));
+//!
+//! let mut value = BTreeMap::new();
+//! value.insert("name", "sam");
+//! value.insert("email", "sam@rcs.com");
+//! value.insert("phone", "123-555-3333");
+//! let model = Model::new(value);
+//!
+//! let list = views.project(&model, "list").unwrap();
+//! assert_eq!(list["key"], model.key.as_ref());
+//! assert_eq!(list["value"]["name"], "sam");
+//! assert_eq!(list["value"]["phone"], "***");
+//! assert!(list["value"].get("email").is_none());
+//! assert!(list.get("version").is_none());
+//!
+//! let admin = views.project(&model, "admin").unwrap();
+//! assert_eq!(admin["value"]["phone"], "123-555-3333");
+//! ```
+//!
+
+use crate::models::Model;
+use crate::redact;
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// The conventional view names.
+pub const LIST: &str = "list";
+pub const PUBLIC: &str = "public";
+pub const ADMIN: &str = "admin";
+
+/// Projection errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectionError {
+    /// no view is declared with the name
+    UnknownView(String),
+    /// the model could not be serialized
+    Json(String),
+}
+
+impl fmt::Display for ProjectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectionError::UnknownView(name) => write!(f, "unknown view: {}", name),
+            ProjectionError::Json(msg) => write!(f, "json error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for ProjectionError {}
+
+impl From<serde_json::Error> for ProjectionError {
+    fn from(err: serde_json::Error) -> Self {
+        ProjectionError::Json(err.to_string())
+    }
+}
+
+/// A named view of a model.  With no included paths the whole model is included; masked paths
+/// are replaced with the mask value, `"***"` by default.  Missing paths are ignored so views can
+/// name optional fields.  An included array item keeps its index, with nulls in place of the
+/// items that aren't included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View {
+    name: String,
+    include: Vec<String>,
+    mask: Vec<String>,
+    mask_value: Value,
+    redacted: bool,
+}
+
+impl View {
+    /// Create a view that includes the whole model.
+    pub fn new(name: &str) -> View {
+        View {
+            name: name.to_string(),
+            include: Vec::new(),
+            mask: Vec::new(),
+            mask_value: Value::from("***"),
+            redacted: false,
+        }
+    }
+
+    /// Include only these paths.
+    pub fn include(mut self, paths: &[&str]) -> View {
+        self.include
+            .extend(paths.iter().map(|path| path.to_string()));
+        self
+    }
+
+    /// Mask these paths.
+    pub fn mask(mut self, paths: &[&str]) -> View {
+        self.mask.extend(paths.iter().map(|path| path.to_string()));
+        self
+    }
+
+    /// Replace masked values with this value, e.g., `Value::Null`.
+    pub fn mask_with(mut self, value: Value) -> View {
+        self.mask_value = value;
+        self
+    }
+
+    /// Serialize with [`crate::redact::to_value`] so every `Sensitive` field is masked.
+    pub fn redacted(mut self) -> View {
+        self.redacted = true;
+        self
+    }
+
+    /// Return the view name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Apply the view to a json document.
+    pub fn apply(&self, doc: &Value) -> Value {
+        let mut out = if self.include.is_empty() {
+            doc.clone()
+        } else {
+            let mut out = Value::Object(Map::new());
+            for path in &self.include {
+                if doc.pointer(path).is_some() {
+                    insert_path(&mut out, doc, path);
+                }
+            }
+            out
+        };
+
+        for path in &self.mask {
+            if let Some(value) = out.pointer_mut(path) {
+                *value = self.mask_value.clone();
+            }
+        }
+
+        out
+    }
+
+    /// Serialize the model and apply the view.
+    pub fn project<T, K>(&self, model: &Model<T, K>) -> Result<Value, ProjectionError>
+    where
+        T: serde::Serialize,
+        K: serde::Serialize,
+    {
+        let doc = if self.redacted {
+            redact::to_value(model)?
+        } else {
+            serde_json::to_value(model)?
+        };
+
+        Ok(self.apply(&doc))
+    }
+}
+
+// copy the value at the pointer from the document, creating the parents along the way with the
+// same shape as the document: an array index stays an array index, padded with nulls so masks
+// and the other included paths keep their positions
+fn insert_path(out: &mut Value, doc: &Value, path: &str) {
+    let mut current = out;
+    let mut source = doc;
+    for token in path.split('/').skip(1) {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        match source {
+            Value::Array(items) => {
+                let idx = match token.parse::<usize>() {
+                    Ok(idx) if idx < items.len() => idx,
+                    _ => return,
+                };
+                if !current.is_array() {
+                    *current = Value::Array(Vec::new());
+                }
+                let list = current.as_array_mut().expect("should be an array");
+                if list.len() <= idx {
+                    list.resize(idx + 1, Value::Null);
+                }
+                current = &mut list[idx];
+                source = &items[idx];
+            }
+            Value::Object(map) => {
+                source = match map.get(&token) {
+                    Some(value) => value,
+                    None => return,
+                };
+                if !current.is_object() {
+                    *current = Value::Object(Map::new());
+                }
+                current = current
+                    .as_object_mut()
+                    .expect("should be an object")
+                    .entry(token)
+                    .or_insert(Value::Null);
+            }
+            _ => return,
+        }
+    }
+
+    *current = source.clone();
+}
+
+/// The views declared by a domain.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Projection {
+    views: Vec<View>,
+}
+
+impl Projection {
+    /// Create a projection with no views.
+    pub fn new() -> Projection {
+        Projection::default()
+    }
+
+    /// Add the view, replacing any view with the same name.
+    pub fn view(mut self, view: View) -> Projection {
+        self.views.retain(|v| v.name != view.name);
+        self.views.push(view);
+        self
+    }
+
+    /// Return the view for the name.
+    pub fn get(&self, name: &str) -> Option<&View> {
+        self.views.iter().find(|view| view.name == name)
+    }
+
+    /// Return the view names in the order they were declared.
+    pub fn names(&self) -> Vec<&str> {
+        self.views.iter().map(|view| view.name()).collect()
+    }
+
+    /// Apply the named view to the model.
+    pub fn project<T, K>(&self, model: &Model<T, K>, name: &str) -> Result<Value, ProjectionError>
+    where
+        T: serde::Serialize,
+        K: serde::Serialize,
+    {
+        match self.get(name) {
+            Some(view) => view.project(model),
+            None => Err(ProjectionError::UnknownView(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::redact::{EmailMask, Sensitive};
+    use serde_json::json;
+
+    #[test]
+    fn include_nested() {
+        let doc = json!({
+            "key": "abc",
+            "value": {"name": "sam", "address": {"city": "seattle", "zip": "98101"}, "tags": [1, 2]}
+        });
+
+        let view =
+            View::new(LIST).include(&["/value/address/city", "/value/tags", "/value/missing"]);
+
+        assert_eq!(
+            view.apply(&doc),
+            json!({"value": {"address": {"city": "seattle"}, "tags": [1, 2]}})
+        );
+    }
+
+    #[test]
+    fn include_array_items() {
+        let doc = json!({
+            "value": {"cards": [{"n": "4111", "exp": "01/30"}, {"n": "5500", "exp": "02/31"}, {"n": "3400"}]}
+        });
+
+        let view =
+            View::new(LIST).include(&["/value/cards/1", "/value/cards/2/n", "/value/cards/9"]);
+        assert_eq!(
+            view.apply(&doc),
+            json!({"value": {"cards": [null, {"n": "5500", "exp": "02/31"}, {"n": "3400"}]}})
+        );
+
+        let view = View::new(PUBLIC)
+            .include(&["/value/cards/0/n", "/value/cards/1/n"])
+            .mask(&["/value/cards/1/n"]);
+        assert_eq!(
+            view.apply(&doc),
+            json!({"value": {"cards": [{"n": "4111"}, {"n": "***"}]}})
+        );
+
+        // a numeric name in an object is still a name
+        let doc = json!({"value": {"1": "a", "2": "b"}});
+        let view = View::new(LIST).include(&["/value/2"]);
+        assert_eq!(view.apply(&doc), json!({"value": {"2": "b"}}));
+    }
+
+    #[test]
+    fn mask_values() {
+        let doc = json!({"value": {"ssn": "123-45-6789", "cards": ["4111", "5500"], "a/b": 1}});
+
+        let view = View::new(PUBLIC)
+            .mask(&[
+                "/value/ssn",
+                "/value/cards/1",
+                "/value/a~1b",
+                "/value/missing",
+            ])
+            .mask_with(Value::Null);
+
+        assert_eq!(
+            view.apply(&doc),
+            json!({"value": {"ssn": null, "cards": ["4111", null], "a/b": null}})
+        );
+    }
+
+    #[test]
+    fn escaped_include() {
+        let doc = json!({"value": {"a/b": 1, "c~d": 2}});
+        let view = View::new(LIST).include(&["/value/a~1b", "/value/c~0d"]);
+
+        assert_eq!(view.apply(&doc), doc);
+    }
+
+    #[test]
+    fn redacted_view() {
+        let value: Sensitive<String, EmailMask> = "dpw@rcs.com".to_string().into();
+        let model = Model::new(value);
+
+        let view = View::new(PUBLIC).include(&["/value"]);
+        assert_eq!(view.project(&model).unwrap()["value"], "dpw@rcs.com");
+
+        let view = view.redacted();
+        assert_eq!(view.project(&model).unwrap()["value"], "d***@rcs.com");
+    }
+
+    #[test]
+    fn projection_views() {
+        let projection = Projection::new()
+            .view(View::new(LIST).include(&["/key"]))
+            .view(View::new(ADMIN))
+            .view(View::new(LIST).include(&["/value"]));
+
+        assert_eq!(projection.names(), vec![ADMIN, LIST]);
+
+        let model = Model::new("my value".to_string());
+        assert_eq!(
+            projection.project(&model, LIST).unwrap(),
+            json!({"value": "my value"})
+        );
+        assert_eq!(
+            projection.project(&model, PUBLIC),
+            Err(ProjectionError::UnknownView(PUBLIC.to_string()))
+        );
+    }
+}
@@ -0,0 +1,324 @@
+//!
+//! Redaction for sensitive model fields, e.g., emails and phone numbers.  Wrap a field in
+//! `Sensitive` to mark it: `Debug` and `Display` always show the masked value, so the field is
+//! safe to log, while normal serialization keeps the full value for persistence and hashing.
+//! Serialize with `redact::to_value` or `redact::to_string` to mask every sensitive field, e.g.,
+//! for an audit log or a support tool.
+//!
+//! The mask is chosen by the marker type: `EmailMask` keeps the first letter and the domain,
+//! `PhoneMask` keeps the last four digits and `SecretMask` hides everything.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::redact::{self, SensitiveEmail, SensitivePhone};
+//! use serde_derive::Serialize;
+//!
+//! #[derive(Debug, Serialize)]
+//! struct Contact {
+//!     name: String,
+//!     email: SensitiveEmail<String>,
+//!     phone: SensitivePhone<String>,
+//! }
+//!
+//! let contact = Contact {
+//!     name: "dpw".to_string(),
+//!     email: "dpw@rcs.com".to_string().into(),
+//!     phone: "123-555-3333".to_string().into(),
+//! };
+//!
+//! assert_eq!(
+//!     format!("{:?}", contact),
+//!     r#"Contact { name: "dpw", email: "d***@rcs.com", phone: "***-***-3333" }"#
+//! );
+//!
+//! let full = serde_json::to_string(&contact).unwrap();
+//! assert_eq!(full, r#"{"name":"dpw","email":"dpw@rcs.com","phone":"123-555-3333"}"#);
+//!
+//! let redacted = redact::to_string(&contact).unwrap();
+//! assert_eq!(redacted, r#"{"name":"dpw","email":"d***@rcs.com","phone":"***-***-3333"}"#);
+//! ```
+//!
+
+use serde::{Serialize, Serializer};
+use serde_derive::Deserialize;
+use serde_json::Value;
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
+use std::ops::Deref;
+
+thread_local! {
+    static REDACTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// How a sensitive value is masked.
+pub trait Mask {
+    /// return the masked value
+    fn mask(value: &str) -> String;
+}
+
+/// Mask an email, keeping the first letter and the domain, e.g., `d***@rcs.com`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EmailMask;
+
+impl Mask for EmailMask {
+    fn mask(value: &str) -> String {
+        mask_email(value)
+    }
+}
+
+/// Mask a phone number, keeping the separators and the last four digits, e.g., `***-***-3333`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PhoneMask;
+
+impl Mask for PhoneMask {
+    fn mask(value: &str) -> String {
+        mask_phone(value)
+    }
+}
+
+/// Mask the whole value.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SecretMask;
+
+impl Mask for SecretMask {
+    fn mask(_: &str) -> String {
+        "***".to_string()
+    }
+}
+
+/// Return the masked email; a value without an `@` is fully masked.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::redact::mask_email;
+///
+/// assert_eq!(mask_email("dpw@rcs.com"), "d***@rcs.com");
+/// assert_eq!(mask_email("not an email"), "***");
+/// ```
+pub fn mask_email(value: &str) -> String {
+    match value.split_once('@') {
+        Some((local, domain)) => match local.chars().next() {
+            Some(first) => format!("{}***@{}", first, domain),
+            None => format!("***@{}", domain),
+        },
+        None => "***".to_string(),
+    }
+}
+
+/// Return the masked phone number; every digit but the last four is replaced with `*`.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::redact::mask_phone;
+///
+/// assert_eq!(mask_phone("123-555-3333"), "***-***-3333");
+/// assert_eq!(mask_phone("+1 (206) 555-1234"), "+* (***) ***-1234");
+/// ```
+pub fn mask_phone(value: &str) -> String {
+    let digits = value.chars().filter(|ch| ch.is_ascii_digit()).count();
+    let mut seen = 0;
+
+    value
+        .chars()
+        .map(|ch| {
+            if !ch.is_ascii_digit() {
+                return ch;
+            }
+            seen += 1;
+            if seen + 4 > digits {
+                ch
+            } else {
+                '*'
+            }
+        })
+        .collect()
+}
+
+/// A sensitive field.  The wrapped value serializes and deserializes as is, except inside
+/// `redact::to_value` and `redact::to_string` where it serializes masked.  `Debug` and
+/// `Display` always show the masked value.
+#[derive(Default, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+#[serde(transparent)]
+pub struct Sensitive<T, M = SecretMask> {
+    value: T,
+    #[serde(skip)]
+    mask: PhantomData<M>,
+}
+
+/// A sensitive email.
+pub type SensitiveEmail<T> = Sensitive<T, EmailMask>;
+
+/// A sensitive phone number.
+pub type SensitivePhone<T> = Sensitive<T, PhoneMask>;
+
+impl<T, M> Sensitive<T, M> {
+    /// Wrap the value.
+    pub fn new(value: T) -> Sensitive<T, M> {
+        Sensitive {
+            value,
+            mask: PhantomData,
+        }
+    }
+
+    /// Return the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T: AsRef<str>, M: Mask> Sensitive<T, M> {
+    /// Return the masked value.
+    pub fn masked(&self) -> String {
+        M::mask(self.value.as_ref())
+    }
+}
+
+impl<T, M> From<T> for Sensitive<T, M> {
+    fn from(value: T) -> Self {
+        Sensitive::new(value)
+    }
+}
+
+impl<T, M> Deref for Sensitive<T, M> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: AsRef<str>, M> AsRef<str> for Sensitive<T, M> {
+    fn as_ref(&self) -> &str {
+        self.value.as_ref()
+    }
+}
+
+impl<T: AsRef<str>, M: Mask> fmt::Debug for Sensitive<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.masked())
+    }
+}
+
+impl<T: AsRef<str>, M: Mask> fmt::Display for Sensitive<T, M> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.masked())
+    }
+}
+
+impl<T: AsRef<str> + Serialize, M: Mask> Serialize for Sensitive<T, M> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if is_redacting() {
+            serializer.serialize_str(&self.masked())
+        } else {
+            self.value.serialize(serializer)
+        }
+    }
+}
+
+/// Return true while a redacted serialization is running on this thread.
+pub fn is_redacting() -> bool {
+    REDACTING.with(|flag| flag.get())
+}
+
+// set the flag for the closure and restore it, even on panic
+fn redacting<R>(f: impl FnOnce() -> R) -> R {
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0;
+            REDACTING.with(|flag| flag.set(previous));
+        }
+    }
+
+    let _restore = Restore(REDACTING.with(|flag| flag.replace(true)));
+
+    f()
+}
+
+/// Serialize the value to json with every sensitive field masked.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value, serde_json::Error> {
+    redacting(|| serde_json::to_value(value))
+}
+
+/// Serialize the value to a json string with every sensitive field masked.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, serde_json::Error> {
+    redacting(|| serde_json::to_string(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Model;
+    use std::borrow::Cow;
+
+    #[derive(
+        Debug, Default, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Hash,
+    )]
+    struct Peep {
+        email: SensitiveEmail<Cow<'static, str>>,
+        name: Cow<'static, str>,
+        phone: SensitivePhone<Cow<'static, str>>,
+        password: Sensitive<String>,
+    }
+
+    fn peep() -> Peep {
+        Peep {
+            email: Cow::from("dpw@rcs.com").into(),
+            name: Cow::from("dpw"),
+            phone: Cow::from("123-555-3333").into(),
+            password: "secret".to_string().into(),
+        }
+    }
+
+    #[test]
+    fn masks() {
+        assert_eq!(mask_email("@rcs.com"), "***@rcs.com");
+        assert_eq!(mask_email(""), "***");
+        assert_eq!(mask_phone("333"), "333");
+        assert_eq!(mask_phone("5551234"), "***1234");
+        assert_eq!(SecretMask::mask("anything"), "***");
+    }
+
+    #[test]
+    fn debug_and_display() {
+        let peep = peep();
+        let debug = format!("{:?}", peep);
+
+        assert!(debug.contains(r#"email: "d***@rcs.com""#));
+        assert!(debug.contains(r#"password: "***""#));
+        assert!(!debug.contains("secret"));
+        assert_eq!(peep.phone.to_string(), "***-***-3333");
+        assert_eq!(peep.password.len(), 6);
+    }
+
+    #[test]
+    fn model_persistence_keeps_values() {
+        let model = Model::new(peep());
+
+        let json = serde_json::to_string(&model).unwrap();
+        assert!(json.contains("dpw@rcs.com"));
+        assert_eq!(serde_json::from_str::<Model<Peep>>(&json).unwrap(), model);
+
+        // the hash is of the full value, so redaction never changes it
+        let redacted = to_value(&model).unwrap();
+        assert_eq!(redacted["value"]["email"], "d***@rcs.com");
+        assert_eq!(redacted["value"]["password"], "***");
+        assert_eq!(Model::calc_hash(&model.value), model.version.hash);
+    }
+
+    #[test]
+    fn flag_restored() {
+        assert!(!is_redacting());
+        let _ = to_string(&peep()).unwrap();
+        assert!(!is_redacting());
+
+        let result = std::panic::catch_unwind(|| redacting(|| panic!("boom")));
+        assert!(result.is_err());
+        assert!(!is_redacting());
+    }
+}
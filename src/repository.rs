@@ -0,0 +1,1510 @@
+//!
+//! The storage contract for models.  A `Repository` stores `Model<T, K>` values keyed by the model's
+//! routing key; backends (redis, sql, in-memory) implement the trait so services can share
+//! a single interface.
+//!
+//! The trait takes `&self` and returns owned values, and implementations must be `Send + Sync`,
+//! so a repository can be shared across threads behind an `Arc`.  Async backends implement
+//! `AsyncRepository`, the same contract with boxed futures, so async services never block an
+//! executor thread on a client call.
+//!
+//! `MemoryRepository` is the reference implementation; use it as a test double and as the
+//! executable definition of the key and `Version` rules every backend should follow.
+//!
+//! Models with an `expires_at` at or before now are expired: backends treat them as missing,
+//! like a redis key past its TTL, and may evict them at any time.
+//!
+
+use crate::history::{EventSink, ModelEvent};
+use crate::keys::{ModelKey, RouteId};
+use crate::models::{
+    DomainModel, Model, ModelError, PurgePolicy, Status, TransitionError, Transitions,
+    VersionConflict, FIRST_SCHEMA, TS,
+};
+use crate::tenant::TenantId;
+use chrono::{Duration, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::future::{self, Future};
+use std::pin::Pin;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Repository errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepositoryError {
+    /// no model exists for the key
+    NotFound(String),
+    /// a model already exists for the key
+    Exists(String),
+    /// the model failed validation
+    Invalid(Vec<ModelError>),
+    /// the update was based on a stale version; another writer updated the model first
+    Conflict {
+        key: String,
+        expected: u64,
+        actual: u64,
+    },
+    /// the status change isn't allowed, e.g., restoring a model that isn't deleted
+    Transition(TransitionError),
+    /// the model for the key belongs to another tenant
+    CrossTenant(String),
+    /// the value can't be serialized to json for its content hash
+    Serialize(String),
+    /// a backend error, e.g., a lost connection
+    Store(String),
+}
+
+impl fmt::Display for RepositoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryError::NotFound(key) => write!(f, "model not found for key: {}", key),
+            RepositoryError::Exists(key) => write!(f, "model already exists for key: {}", key),
+            RepositoryError::Invalid(errors) => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "invalid model: {}", messages.join(", "))
+            }
+            RepositoryError::Conflict {
+                key,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "version conflict for key: {}, expected update count {} but found {}",
+                key, expected, actual
+            ),
+            RepositoryError::Transition(err) => write!(f, "{}", err),
+            RepositoryError::CrossTenant(key) => {
+                write!(f, "model belongs to another tenant for key: {}", key)
+            }
+            RepositoryError::Serialize(msg) => write!(f, "serialize error: {}", msg),
+            RepositoryError::Store(msg) => write!(f, "store error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for RepositoryError {}
+
+impl From<serde_json::Error> for RepositoryError {
+    fn from(err: serde_json::Error) -> Self {
+        RepositoryError::Serialize(err.to_string())
+    }
+}
+
+impl From<TransitionError> for RepositoryError {
+    fn from(err: TransitionError) -> Self {
+        RepositoryError::Transition(err)
+    }
+}
+
+impl RepositoryError {
+    /// Create a conflict error for the key from a version conflict.
+    pub fn conflict(key: String, conflict: VersionConflict) -> RepositoryError {
+        RepositoryError::Conflict {
+            key,
+            expected: conflict.expected,
+            actual: conflict.actual,
+        }
+    }
+}
+
+/// The storage contract for `Model<T, K>` keyed by the model key, `RouteId` by default.
+pub trait Repository<T, K = RouteId>: Send + Sync {
+    /// return the model for the key, or None if it doesn't exist or has expired; soft deleted
+    /// models are returned so they can be restored
+    fn get(&self, key: &str) -> Result<Option<Model<T, K>>, RepositoryError>;
+
+    /// store the value as a new model with a new key and version and return the model
+    fn insert(&self, value: T) -> Result<Model<T, K>, RepositoryError>;
+
+    /// replace an existing model and return the stored model with its new version; a status
+    /// change must be allowed by `transitions`, and a move out of Deleted is a restore
+    fn update(&self, model: Model<T, K>) -> Result<Model<T, K>, RepositoryError>;
+
+    /// remove the model for the key and return it, or None if it didn't exist; this is a hard
+    /// delete, see `soft_delete`
+    fn delete(&self, key: &str) -> Result<Option<Model<T, K>>, RepositoryError>;
+
+    /// return all models that haven't expired, including soft deleted models
+    fn list_all(&self) -> Result<Vec<Model<T, K>>, RepositoryError>;
+
+    /// the status transition table for the stored models; `update` and `restore` check it
+    fn transitions(&self) -> Transitions {
+        Transitions::default()
+    }
+
+    /// return all models that aren't soft deleted
+    fn list(&self) -> Result<Vec<Model<T, K>>, RepositoryError> {
+        let mut list = self.list_all()?;
+        list.retain(|model| !model.is_deleted());
+
+        Ok(list)
+    }
+
+    /// Soft delete the model for the key with `Model::mark_deleted` and return the stored model.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::models::{Status, StatusFlags};
+    /// use domain_keys::repository::{MemoryRepository, Repository};
+    ///
+    /// let repo = MemoryRepository::new();
+    /// let model = repo.insert("my value".to_string()).unwrap();
+    ///
+    /// let deleted = repo.soft_delete(&model.key).unwrap();
+    /// assert!(deleted.is_deleted());
+    /// assert!(repo.list().unwrap().is_empty());
+    /// assert_eq!(repo.list_all().unwrap().len(), 1);
+    ///
+    /// let restored = repo.restore(&model.key, Status::Active(StatusFlags::NONE)).unwrap();
+    /// assert_eq!(repo.list().unwrap(), vec![restored]);
+    /// ```
+    fn soft_delete(&self, key: &str) -> Result<Model<T, K>, RepositoryError>
+    where
+        T: Clone,
+        K: Clone,
+    {
+        let current = match self.get(key)? {
+            Some(current) => current,
+            None => return Err(RepositoryError::NotFound(key.to_string())),
+        };
+
+        if current.is_deleted() {
+            return Ok(current);
+        }
+
+        // update bumps the version it read, not the one mark_deleted bumped
+        let mut model = current.mark_deleted();
+        model.version = current.version;
+
+        self.update(model)
+    }
+
+    /// Restore the soft deleted model for the key to the status and return the stored model;
+    /// the repository's `transitions` must allow restoring to the status.
+    fn restore(&self, key: &str, to: Status) -> Result<Model<T, K>, RepositoryError>
+    where
+        T: Clone,
+        K: Clone,
+    {
+        let current = match self.get(key)? {
+            Some(current) => current,
+            None => return Err(RepositoryError::NotFound(key.to_string())),
+        };
+
+        let mut model = current.restore_with(to, &self.transitions())?;
+        model.version = current.version;
+
+        self.update(model)
+    }
+
+    /// Hard delete every soft deleted model the policy allows and return the purged models.
+    fn purge(&self, policy: &PurgePolicy, now: TS) -> Result<Vec<Model<T, K>>, RepositoryError>
+    where
+        K: ModelKey,
+    {
+        let mut purged = Vec::new();
+        for model in self.list_all()? {
+            if policy.is_purgeable(&model, now) {
+                if let Some(model) = self.delete(&model.key)? {
+                    purged.push(model);
+                }
+            }
+        }
+
+        Ok(purged)
+    }
+
+    /// Compare and swap: replace the value for the key only if the stored update count still
+    /// matches the one the caller read, otherwise return `RepositoryError::Conflict`.
+    fn compare_and_swap(
+        &self,
+        key: &str,
+        expected_update_count: u64,
+        value: T,
+    ) -> Result<Model<T, K>, RepositoryError> {
+        let current = match self.get(key)? {
+            Some(current) => current,
+            None => return Err(RepositoryError::NotFound(key.to_string())),
+        };
+
+        let mut model = current;
+        model.version.update_count = expected_update_count;
+        model.value = value;
+
+        self.update(model)
+    }
+
+    /// Read, merge and compare and swap in a retry loop.  The merge hook is called with the
+    /// current model and returns the new value; on conflict the model is re-read and merge is
+    /// called again, up to max_attempts times.  The last conflict is returned if every
+    /// attempt fails.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::repository::{MemoryRepository, Repository};
+    ///
+    /// let repo = MemoryRepository::new();
+    /// let model = repo.insert(10_u64).unwrap();
+    ///
+    /// let updated = repo.update_with(&model.key, 5, |current| current.value + 1).unwrap();
+    ///
+    /// assert_eq!(updated.value, 11);
+    /// assert_eq!(updated.version.update_count, 1);
+    /// ```
+    fn update_with<F>(
+        &self,
+        key: &str,
+        max_attempts: usize,
+        mut merge: F,
+    ) -> Result<Model<T, K>, RepositoryError>
+    where
+        F: FnMut(&Model<T, K>) -> T,
+        Self: Sized,
+    {
+        let mut last_conflict = RepositoryError::NotFound(key.to_string());
+
+        for _ in 0..max_attempts.max(1) {
+            let current = match self.get(key)? {
+                Some(current) => current,
+                None => return Err(RepositoryError::NotFound(key.to_string())),
+            };
+
+            let value = merge(&current);
+            match self.compare_and_swap(key, current.version.update_count, value) {
+                Err(err @ RepositoryError::Conflict { .. }) => last_conflict = err,
+                result => return result,
+            }
+        }
+
+        Err(last_conflict)
+    }
+}
+
+/// The boxed future returned by `AsyncRepository` methods.
+pub type RepositoryFuture<'a, R> =
+    Pin<Box<dyn Future<Output = Result<R, RepositoryError>> + Send + 'a>>;
+
+/// The async storage contract for `Model<T, K>`, with the same rules as `Repository`.  Methods
+/// return boxed futures so the trait works without async fn in traits; the default methods
+/// mirror the `Repository` defaults, so a backend only implements the five storage methods.
+pub trait AsyncRepository<T, K = RouteId>: Send + Sync
+where
+    T: Send + 'static,
+    K: Send + 'static,
+{
+    /// return the model for the key, or None if it doesn't exist or has expired
+    fn get<'a>(&'a self, key: &'a str) -> RepositoryFuture<'a, Option<Model<T, K>>>;
+
+    /// store the value as a new model with a new key and version and return the model
+    fn insert(&self, value: T) -> RepositoryFuture<'_, Model<T, K>>;
+
+    /// replace an existing model and return the stored model with its new version
+    fn update(&self, model: Model<T, K>) -> RepositoryFuture<'_, Model<T, K>>;
+
+    /// remove the model for the key and return it, or None if it didn't exist
+    fn delete<'a>(&'a self, key: &'a str) -> RepositoryFuture<'a, Option<Model<T, K>>>;
+
+    /// return all models that haven't expired, including soft deleted models
+    fn list_all(&self) -> RepositoryFuture<'_, Vec<Model<T, K>>>;
+
+    /// the status transition table for the stored models, see `Repository::transitions`
+    fn transitions(&self) -> Transitions {
+        Transitions::default()
+    }
+
+    /// return all models that aren't soft deleted
+    fn list(&self) -> RepositoryFuture<'_, Vec<Model<T, K>>> {
+        Box::pin(async move {
+            let mut list = self.list_all().await?;
+            list.retain(|model| !model.is_deleted());
+
+            Ok(list)
+        })
+    }
+
+    /// Soft delete the model for the key, see `Repository::soft_delete`.
+    fn soft_delete<'a>(&'a self, key: &'a str) -> RepositoryFuture<'a, Model<T, K>>
+    where
+        T: Clone,
+        K: Clone,
+    {
+        Box::pin(async move {
+            let current = match self.get(key).await? {
+                Some(current) => current,
+                None => return Err(RepositoryError::NotFound(key.to_string())),
+            };
+
+            if current.is_deleted() {
+                return Ok(current);
+            }
+
+            // update bumps the version it read, not the one mark_deleted bumped
+            let mut model = current.mark_deleted();
+            model.version = current.version;
+
+            self.update(model).await
+        })
+    }
+
+    /// Restore the soft deleted model for the key to the status, see `Repository::restore`.
+    fn restore<'a>(&'a self, key: &'a str, to: Status) -> RepositoryFuture<'a, Model<T, K>>
+    where
+        T: Clone,
+        K: Clone,
+    {
+        Box::pin(async move {
+            let current = match self.get(key).await? {
+                Some(current) => current,
+                None => return Err(RepositoryError::NotFound(key.to_string())),
+            };
+
+            let mut model = current.restore_with(to, &self.transitions())?;
+            model.version = current.version;
+
+            self.update(model).await
+        })
+    }
+
+    /// Hard delete every soft deleted model the policy allows, see `Repository::purge`.
+    fn purge<'a>(
+        &'a self,
+        policy: &'a PurgePolicy,
+        now: TS,
+    ) -> RepositoryFuture<'a, Vec<Model<T, K>>>
+    where
+        K: ModelKey,
+    {
+        Box::pin(async move {
+            let mut purged = Vec::new();
+            for model in self.list_all().await? {
+                if policy.is_purgeable(&model, now) {
+                    if let Some(model) = self.delete(&model.key).await? {
+                        purged.push(model);
+                    }
+                }
+            }
+
+            Ok(purged)
+        })
+    }
+
+    /// Replace the value only if the stored update count still matches, see
+    /// `Repository::compare_and_swap`.
+    fn compare_and_swap<'a>(
+        &'a self,
+        key: &'a str,
+        expected_update_count: u64,
+        value: T,
+    ) -> RepositoryFuture<'a, Model<T, K>> {
+        Box::pin(async move {
+            let current = match self.get(key).await? {
+                Some(current) => current,
+                None => return Err(RepositoryError::NotFound(key.to_string())),
+            };
+
+            let mut model = current;
+            model.version.update_count = expected_update_count;
+            model.value = value;
+
+            self.update(model).await
+        })
+    }
+
+    /// Read, merge and compare and swap in a retry loop, see `Repository::update_with`.
+    fn update_with<'a, F>(
+        &'a self,
+        key: &'a str,
+        max_attempts: usize,
+        mut merge: F,
+    ) -> RepositoryFuture<'a, Model<T, K>>
+    where
+        F: FnMut(&Model<T, K>) -> T + Send + 'a,
+        Self: Sized,
+    {
+        Box::pin(async move {
+            let mut last_conflict = RepositoryError::NotFound(key.to_string());
+
+            for _ in 0..max_attempts.max(1) {
+                let current = match self.get(key).await? {
+                    Some(current) => current,
+                    None => return Err(RepositoryError::NotFound(key.to_string())),
+                };
+
+                let value = merge(&current);
+                match self
+                    .compare_and_swap(key, current.version.update_count, value)
+                    .await
+                {
+                    Err(err @ RepositoryError::Conflict { .. }) => last_conflict = err,
+                    result => return result,
+                }
+            }
+
+            Err(last_conflict)
+        })
+    }
+}
+
+/// Validation function for models, e.g., `DomainModel::validate`.
+pub type Validator<T, K = RouteId> = fn(&Model<T, K>) -> Vec<ModelError>;
+
+// models by key string
+type Models<T, K> = HashMap<String, Model<T, K>>;
+
+fn get_now() -> TS {
+    Utc::now().naive_utc()
+}
+
+/// An in-memory repository backed by a `HashMap` behind a `RwLock`.
+///
+/// * insert creates a new model with a new routing key and `Version::new`
+/// * update requires the stored update count, bumps it with `Version::update` and rejects stale versions
+/// * update checks status changes against the repository's `Transitions` and sets `deleted_at`
+///   from the status, see `with_transitions`
+/// * insert stamps the repository's schema version, see `with_schema` and `for_domain`
+/// * an update with an unchanged value, schema and status is a no-op and returns the stored model
+/// * list returns the models that aren't soft deleted in the order they were created
+/// * with a sink, every insert, update and delete emits a `ModelEvent`; no-op updates don't
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::repository::{MemoryRepository, Repository, RepositoryError};
+///
+/// let repo = MemoryRepository::new();
+/// let model = repo.insert("my value".to_string()).unwrap();
+///
+/// assert_eq!(model.key.len(), 16);
+/// assert_eq!(model.version.update_count, 0);
+///
+/// let mut changed = model.clone();
+/// changed.value = "new value".to_string();
+/// let updated = repo.update(changed).unwrap();
+/// assert_eq!(updated.version.update_count, 1);
+///
+/// // the original model is now stale
+/// match repo.update(model) {
+///     Err(RepositoryError::Conflict { expected, actual, .. }) => {
+///         assert_eq!(expected, 0);
+///         assert_eq!(actual, 1);
+///     }
+///     other => panic!("should conflict: {:?}", other),
+/// }
+/// ```
+pub struct MemoryRepository<T, K = RouteId> {
+    models: Arc<RwLock<Models<T, K>>>,
+    validator: Option<Validator<T, K>>,
+    sink: Option<Arc<dyn EventSink>>,
+    tenant: Option<TenantId>,
+    ttl: Option<Duration>,
+    schema: u16,
+    transitions: Transitions,
+}
+
+impl<T> MemoryRepository<T> {
+    /// Create a new empty repository of routing key models without validation.  For other key
+    /// types use `default`, e.g., `MemoryRepository::<User, UserKey>::default()`.
+    pub fn new() -> MemoryRepository<T> {
+        MemoryRepository::default()
+    }
+}
+
+impl<T: DomainModel> MemoryRepository<T, T::Key> {
+    /// Create a new empty repository for the domain: models are keyed by the domain's key type,
+    /// validated with `DomainModel::validate`, inserted at `DomainModel::SCHEMA` and restored
+    /// with `DomainModel::transitions`.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::keys::RouteId;
+    /// use domain_keys::models::{DomainModel, Model, ModelError};
+    /// use domain_keys::repository::{MemoryRepository, Repository};
+    ///
+    /// #[derive(Debug, Clone, serde::Serialize)]
+    /// struct Invite(String);
+    ///
+    /// impl DomainModel for Invite {
+    ///     type Key = RouteId;
+    ///
+    ///     const SCHEMA: u16 = 2;
+    ///
+    ///     fn validate(_: &Model<Self>) -> Vec<ModelError> {
+    ///         vec![]
+    ///     }
+    /// }
+    ///
+    /// let repo = MemoryRepository::for_domain();
+    /// let model = repo.insert(Invite("sam@rcs.com".to_string())).unwrap();
+    /// assert_eq!(model.schema, 2);
+    /// ```
+    pub fn for_domain() -> MemoryRepository<T, T::Key> {
+        MemoryRepository::default()
+            .with_validator(T::validate)
+            .with_schema(T::SCHEMA)
+            .with_transitions(T::transitions())
+    }
+}
+
+impl<T, K> MemoryRepository<T, K> {
+    /// Insert models at the value's schema version, see [`crate::schema`]; the default is
+    /// `FIRST_SCHEMA`.  Updates keep the schema of the model they're given.
+    pub fn with_schema(mut self, schema: u16) -> MemoryRepository<T, K> {
+        self.schema = schema;
+        self
+    }
+
+    /// Check status changes against the table; the default is `Transitions::default`.
+    pub fn with_transitions(mut self, transitions: Transitions) -> MemoryRepository<T, K> {
+        self.transitions = transitions;
+        self
+    }
+
+    /// Validate models on insert and update; invalid models are rejected with
+    /// `RepositoryError::Invalid`.
+    pub fn with_validator(mut self, validator: Validator<T, K>) -> MemoryRepository<T, K> {
+        self.validator = Some(validator);
+        self
+    }
+
+    /// Emit a `ModelEvent` to the sink for every write.  Events are emitted while the write lock
+    /// is held, so the sink sees writes in the order they were made.  A sink error fails the
+    /// operation with `RepositoryError::Store` after the write, i.e., the write isn't rolled back.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::history::MemorySink;
+    /// use domain_keys::repository::{MemoryRepository, Repository};
+    /// use std::sync::Arc;
+    ///
+    /// let sink = Arc::new(MemorySink::new());
+    /// let repo = MemoryRepository::new().with_sink(sink.clone());
+    ///
+    /// let model = repo.insert("my value".to_string()).unwrap();
+    /// repo.delete(&model.key).unwrap();
+    ///
+    /// let events = sink.events_for(&model.key);
+    /// assert_eq!(events.len(), 2);
+    /// assert_eq!(events[1].new_version, None);
+    /// ```
+    pub fn with_sink(mut self, sink: Arc<dyn EventSink>) -> MemoryRepository<T, K> {
+        self.sink = Some(sink);
+        self
+    }
+
+    /// Set inserted models to expire after the time to live, e.g., for sessions or invites.
+    /// Expired models are treated as missing until they are removed with `purge_expired`.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use chrono::Duration;
+    /// use domain_keys::repository::{MemoryRepository, Repository};
+    ///
+    /// let sessions = MemoryRepository::new().with_ttl(Duration::minutes(30));
+    /// let session = sessions.insert("token".to_string()).unwrap();
+    /// assert!(session.expires_at.is_some());
+    ///
+    /// // extend the session by another 30 minutes
+    /// let mut extended = session.extend(Duration::minutes(30));
+    /// extended.version = session.version.clone();
+    /// let extended = sessions.update(extended).unwrap();
+    /// assert!(extended.expires_at > session.expires_at);
+    /// ```
+    pub fn with_ttl(mut self, ttl: Duration) -> MemoryRepository<T, K> {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// Return a repository scoped to the tenant that shares this repository's store, validator
+    /// and sink.  Inserts are owned by the tenant, lists only include the tenant's models, and
+    /// reading, updating or deleting another tenant's model fails with
+    /// `RepositoryError::CrossTenant`.  An unscoped repository sees every tenant's models.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::repository::{MemoryRepository, Repository, RepositoryError};
+    /// use domain_keys::tenant::TenantId;
+    ///
+    /// let store = MemoryRepository::new();
+    /// let acme = store.for_tenant(TenantId::new("acme").unwrap());
+    /// let globex = store.for_tenant(TenantId::new("globex").unwrap());
+    ///
+    /// let model = acme.insert("my value".to_string()).unwrap();
+    /// assert_eq!(model.tenant.as_deref(), Some("acme"));
+    /// assert_eq!(acme.list().unwrap(), vec![model.clone()]);
+    /// assert!(globex.list().unwrap().is_empty());
+    ///
+    /// match globex.get(&model.key) {
+    ///     Err(RepositoryError::CrossTenant(key)) => assert_eq!(key, model.key),
+    ///     other => panic!("should fail: {:?}", other),
+    /// }
+    /// ```
+    pub fn for_tenant(&self, tenant: TenantId) -> MemoryRepository<T, K> {
+        MemoryRepository {
+            models: self.models.clone(),
+            validator: self.validator,
+            sink: self.sink.clone(),
+            tenant: Some(tenant),
+            ttl: self.ttl,
+            schema: self.schema,
+            transitions: self.transitions,
+        }
+    }
+
+    /// Return the tenant the repository is scoped to, if any.
+    pub fn tenant(&self) -> Option<&TenantId> {
+        self.tenant.as_ref()
+    }
+
+    /// Migration helper: re-hash every stored model with the current content hash and return
+    /// the number of models changed.  Versions are not bumped.
+    pub fn rehash(&self) -> Result<usize, RepositoryError>
+    where
+        T: Clone + Serialize,
+        K: ModelKey,
+    {
+        let mut models = self.write()?;
+        let mut count = 0;
+        for model in models.values_mut().filter(|model| self.is_visible(model)) {
+            if let Some(rehashed) = model.try_rehash()? {
+                *model = rehashed;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+
+    /// Return the number of stored models that haven't expired, or the tenant's models when
+    /// scoped.
+    pub fn len(&self) -> usize {
+        let now = get_now();
+        self.read()
+            .map(|models| {
+                models
+                    .values()
+                    .filter(|model| self.is_visible(model) && !model.is_expired(now))
+                    .count()
+            })
+            .unwrap_or(0)
+    }
+
+    /// Remove the expired models, emitting a delete event for each, and return the number
+    /// removed.
+    pub fn purge_expired(&self, now: TS) -> Result<usize, RepositoryError>
+    where
+        T: Serialize,
+        K: fmt::Display,
+    {
+        let mut models = self.write()?;
+        let expired: Vec<String> = models
+            .iter()
+            .filter(|(_, model)| self.is_visible(model) && model.is_expired(now))
+            .map(|(key, _)| key.clone())
+            .collect();
+
+        for key in &expired {
+            if let Some(model) = models.remove(key) {
+                self.emit(Some(&model), None)?;
+            }
+        }
+
+        Ok(expired.len())
+    }
+
+    /// Return true if there are no stored models.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn read(&self) -> Result<RwLockReadGuard<'_, Models<T, K>>, RepositoryError> {
+        self.models
+            .read()
+            .map_err(|_| RepositoryError::Store("lock poisoned".to_string()))
+    }
+
+    fn write(&self) -> Result<RwLockWriteGuard<'_, Models<T, K>>, RepositoryError> {
+        self.models
+            .write()
+            .map_err(|_| RepositoryError::Store("lock poisoned".to_string()))
+    }
+
+    fn emit(
+        &self,
+        old: Option<&Model<T, K>>,
+        new: Option<&Model<T, K>>,
+    ) -> Result<(), RepositoryError>
+    where
+        T: Serialize,
+        K: fmt::Display,
+    {
+        let sink = match &self.sink {
+            Some(sink) => sink,
+            None => return Ok(()),
+        };
+
+        let event = ModelEvent::new(old, new).map_err(|e| RepositoryError::Store(e.to_string()))?;
+        sink.emit(&event)
+            .map_err(|e| RepositoryError::Store(format!("event sink: {}", e)))
+    }
+
+    // an unscoped repository sees every model
+    fn is_visible(&self, model: &Model<T, K>) -> bool {
+        self.tenant.is_none() || model.belongs_to(self.tenant.as_ref())
+    }
+
+    fn check_tenant(&self, key: &str, model: &Model<T, K>) -> Result<(), RepositoryError> {
+        if self.is_visible(model) {
+            Ok(())
+        } else {
+            Err(RepositoryError::CrossTenant(key.to_string()))
+        }
+    }
+
+    fn validate(&self, model: &Model<T, K>) -> Result<(), RepositoryError> {
+        let errors = match self.validator {
+            Some(validator) => validator(model),
+            None => return Ok(()),
+        };
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(RepositoryError::Invalid(errors))
+        }
+    }
+}
+
+impl<T, K> Default for MemoryRepository<T, K> {
+    fn default() -> Self {
+        MemoryRepository {
+            models: Arc::new(RwLock::new(HashMap::new())),
+            validator: None,
+            sink: None,
+            tenant: None,
+            ttl: None,
+            schema: FIRST_SCHEMA,
+            transitions: Transitions::default(),
+        }
+    }
+}
+
+impl<T, K> Repository<T, K> for MemoryRepository<T, K>
+where
+    T: Clone + Serialize + Send + Sync,
+    K: ModelKey,
+{
+    fn get(&self, key: &str) -> Result<Option<Model<T, K>>, RepositoryError> {
+        match self.read()?.get(key) {
+            Some(model) if !model.is_expired(get_now()) => {
+                self.check_tenant(key, model)?;
+                Ok(Some(model.clone()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn insert(&self, value: T) -> Result<Model<T, K>, RepositoryError> {
+        let mut model = Model::try_new_keyed(value)?.with_schema(self.schema);
+        model.tenant = self.tenant.clone();
+        if let Some(ttl) = self.ttl {
+            model = model.with_ttl(ttl);
+        }
+        self.validate(&model)?;
+
+        let mut models = self.write()?;
+        let key = model.key.to_string();
+        if models.contains_key(&key) {
+            return Err(RepositoryError::Exists(key));
+        }
+
+        models.insert(key, model.clone());
+        self.emit(None, Some(&model))?;
+
+        Ok(model)
+    }
+
+    fn update(&self, model: Model<T, K>) -> Result<Model<T, K>, RepositoryError> {
+        self.validate(&model)?;
+
+        let mut models = self.write()?;
+        let key = model.key.to_string();
+        let stored = match models.get(&key) {
+            Some(stored) if !stored.is_expired(get_now()) => stored,
+            _ => return Err(RepositoryError::NotFound(key)),
+        };
+
+        // the model can't move between tenants
+        self.check_tenant(&key, stored)?;
+        if model.tenant != stored.tenant {
+            return Err(RepositoryError::CrossTenant(key));
+        }
+
+        let hash = Model::try_calc_hash(&model.value)?;
+        let version = match stored
+            .version
+            .compare_and_update(model.version.update_count, hash)
+        {
+            Ok(version) => version,
+            Err(conflict) => return Err(RepositoryError::conflict(key, conflict)),
+        };
+
+        // the status follows the transition table and deleted_at follows the status
+        let deleted_at = stored.stored_deleted_at(&model.status, &self.transitions)?;
+
+        // skip no-op writes; the stored model is returned as is
+        if hash == stored.version.hash
+            && model.schema == stored.schema
+            && model.status == stored.status
+            && deleted_at == stored.deleted_at
+            && model.expires_at == stored.expires_at
+        {
+            return Ok(stored.clone());
+        }
+
+        let mut updated =
+            Model::create_model(model.key.clone(), &version, &model.status, &model.value);
+        updated.schema = model.schema;
+        updated.deleted_at = deleted_at;
+        updated.tenant = model.tenant;
+        updated.expires_at = model.expires_at;
+        let previous = models.insert(key, updated.clone());
+        self.emit(previous.as_ref(), Some(&updated))?;
+
+        Ok(updated)
+    }
+
+    fn delete(&self, key: &str) -> Result<Option<Model<T, K>>, RepositoryError> {
+        let mut models = self.write()?;
+        if let Some(model) = models.get(key) {
+            self.check_tenant(key, model)?;
+        }
+
+        let deleted = models.remove(key);
+        if let Some(model) = &deleted {
+            self.emit(Some(model), None)?;
+        }
+
+        // an expired model is removed but reported as missing
+        Ok(deleted.filter(|model| !model.is_expired(get_now())))
+    }
+
+    fn transitions(&self) -> Transitions {
+        self.transitions
+    }
+
+    fn list_all(&self) -> Result<Vec<Model<T, K>>, RepositoryError> {
+        let now = get_now();
+        let mut list: Vec<Model<T, K>> = self
+            .read()?
+            .values()
+            .filter(|model| self.is_visible(model) && !model.is_expired(now))
+            .cloned()
+            .collect();
+        list.sort_by(|a, b| {
+            a.version
+                .created_at
+                .cmp(&b.version.created_at)
+                .then_with(|| a.key.cmp(&b.key))
+        });
+
+        Ok(list)
+    }
+}
+
+// the memory repository never waits, so every future is ready
+impl<T, K> AsyncRepository<T, K> for MemoryRepository<T, K>
+where
+    T: Clone + Serialize + Send + Sync + 'static,
+    K: ModelKey + Send + Sync + 'static,
+{
+    fn get<'a>(&'a self, key: &'a str) -> RepositoryFuture<'a, Option<Model<T, K>>> {
+        Box::pin(future::ready(Repository::get(self, key)))
+    }
+
+    fn insert(&self, value: T) -> RepositoryFuture<'_, Model<T, K>> {
+        Box::pin(future::ready(Repository::insert(self, value)))
+    }
+
+    fn update(&self, model: Model<T, K>) -> RepositoryFuture<'_, Model<T, K>> {
+        Box::pin(future::ready(Repository::update(self, model)))
+    }
+
+    fn delete<'a>(&'a self, key: &'a str) -> RepositoryFuture<'a, Option<Model<T, K>>> {
+        Box::pin(future::ready(Repository::delete(self, key)))
+    }
+
+    fn list_all(&self) -> RepositoryFuture<'_, Vec<Model<T, K>>> {
+        Box::pin(future::ready(Repository::list_all(self)))
+    }
+
+    fn transitions(&self) -> Transitions {
+        self.transitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // not a glob: AsyncRepository methods would make every call ambiguous
+    use super::{MemoryRepository, Repository, RepositoryError};
+    use crate::history::{EventSink, MemorySink, ModelEvent};
+    use crate::models::{
+        Model, ModelError, PurgePolicy, Status, StatusFlags, StatusKind, TransitionError,
+        Transitions,
+    };
+    use crate::tenant::TenantId;
+    use chrono::{Duration, Utc};
+    use std::borrow::Cow;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    fn no_empty_strings(model: &Model<String>) -> Vec<ModelError> {
+        if model.value.is_empty() {
+            vec![ModelError::Email(Cow::from(""))]
+        } else {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn json_values() {
+        let repo: MemoryRepository<serde_json::Value> = MemoryRepository::new();
+        let model = repo
+            .insert(serde_json::json!({"score": 0.5, "tags": ["a"]}))
+            .unwrap();
+
+        let mut changed = model.clone();
+        changed.value["score"] = serde_json::json!(0.75);
+        let updated = repo.update(changed).unwrap();
+        assert_eq!(updated.version.update_count, 1);
+        assert_eq!(repo.get(&model.key).unwrap(), Some(updated));
+    }
+
+    #[test]
+    fn domain_keys() {
+        use crate::keys::{DomainId, KeyDomain};
+
+        struct User;
+        impl KeyDomain for User {
+            const PREFIX: &'static str = "usr";
+        }
+
+        let repo = MemoryRepository::<String, DomainId<User>>::default();
+        let model = repo.insert("dpw".to_string()).unwrap();
+        assert!(model.key.starts_with("usr_"));
+
+        let found = repo.get(&model.key).unwrap().expect("should find model");
+        assert_eq!(found, model);
+
+        let mut updated = found.clone();
+        updated.value = "sam".to_string();
+        let updated = repo.update(updated).unwrap();
+        assert_eq!(updated.key, model.key);
+        assert_eq!(updated.version.update_count, 1);
+
+        // a model from another domain doesn't deserialize
+        let json = serde_json::to_string(&Model::new("dpw".to_string())).unwrap();
+        assert!(serde_json::from_str::<Model<String, DomainId<User>>>(&json).is_err());
+    }
+
+    #[test]
+    fn insert_get() {
+        let repo = MemoryRepository::new();
+        assert!(repo.is_empty());
+
+        let model = repo.insert("flarb".to_string()).unwrap();
+        assert_eq!(repo.len(), 1);
+        assert_eq!(model.version.update_count, 0);
+        assert_eq!(model.version.hash, Model::calc_hash(&model.value));
+
+        let found = repo.get(&model.key).unwrap().expect("should find model");
+        assert_eq!(found, model);
+
+        assert!(repo.get("no-such-key").unwrap().is_none());
+    }
+
+    #[test]
+    fn update() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert("flarb".to_string()).unwrap();
+
+        let mut changed = model.clone();
+        changed.value = "changed".to_string();
+        let updated = repo.update(changed.clone()).unwrap();
+
+        assert_eq!(updated.key, model.key);
+        assert_eq!(updated.version.created_at, model.version.created_at);
+        assert_eq!(updated.version.update_count, 1);
+        assert_eq!(updated.version.hash, Model::calc_hash(&updated.value));
+        assert_eq!(repo.get(&model.key).unwrap(), Some(updated.clone()));
+
+        // a second update from the same read is stale
+        let err = repo.update(changed).unwrap_err();
+        assert_eq!(
+            err,
+            RepositoryError::Conflict {
+                key: model.key.to_string(),
+                expected: 0,
+                actual: 1
+            }
+        );
+
+        // but the updated model can be updated again
+        let mut changed = updated;
+        changed.value = "changed again".to_string();
+        let updated = repo.update(changed).unwrap();
+        assert_eq!(updated.version.update_count, 2);
+    }
+
+    #[test]
+    fn compare_and_swap() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert(1_u64).unwrap();
+
+        let updated = repo.compare_and_swap(&model.key, 0, 2).unwrap();
+        assert_eq!(updated.value, 2);
+        assert_eq!(updated.version.update_count, 1);
+
+        let err = repo.compare_and_swap(&model.key, 0, 3).unwrap_err();
+        assert!(matches!(
+            err,
+            RepositoryError::Conflict {
+                expected: 0,
+                actual: 1,
+                ..
+            }
+        ));
+
+        assert!(matches!(
+            repo.compare_and_swap("missing", 0, 3),
+            Err(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn update_with_retries() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert(1_u64).unwrap();
+        let mut calls = 0;
+
+        // a competing writer sneaks in on the first attempt
+        let updated = repo
+            .update_with(&model.key, 3, |current| {
+                calls += 1;
+                if calls == 1 {
+                    repo.compare_and_swap(&model.key, 0, 100).unwrap();
+                }
+                current.value + 1
+            })
+            .unwrap();
+
+        assert_eq!(calls, 2);
+        assert_eq!(updated.value, 101);
+        assert_eq!(updated.version.update_count, 2);
+    }
+
+    #[test]
+    fn update_with_gives_up() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert(1_u64).unwrap();
+
+        let err = repo
+            .update_with(&model.key, 2, |current| {
+                let count = current.version.update_count;
+                repo.compare_and_swap(&model.key, count, current.value + 10)
+                    .unwrap();
+                current.value + 1
+            })
+            .unwrap_err();
+
+        assert!(matches!(err, RepositoryError::Conflict { .. }));
+    }
+
+    #[test]
+    fn rehash() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert(1_u64).unwrap();
+        repo.insert(2_u64).unwrap();
+
+        // simulate a model stored with the old DefaultHasher hash
+        {
+            let mut models = repo.write().unwrap();
+            models.get_mut(model.key.as_ref()).unwrap().version.hash = 99;
+        }
+
+        assert_eq!(repo.rehash().unwrap(), 1);
+        assert_eq!(repo.rehash().unwrap(), 0);
+        assert_eq!(repo.get(&model.key).unwrap(), Some(model));
+    }
+
+    #[test]
+    fn update_unchanged() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert(1_u64).unwrap();
+
+        let same = repo.update(model.clone()).unwrap();
+        assert_eq!(same, model);
+
+        // a status change is still a write
+        let mut changed = model.clone();
+        changed.status = Status::Active(StatusFlags::NONE);
+        let updated = repo.update(changed).unwrap();
+        assert_eq!(updated.version.update_count, 1);
+
+        // stale no-op writes still conflict
+        assert!(matches!(
+            repo.update(model),
+            Err(RepositoryError::Conflict { .. })
+        ));
+    }
+
+    #[test]
+    fn update_not_found() {
+        let repo: MemoryRepository<String> = MemoryRepository::new();
+        let model = Model::new("orphan".to_string());
+
+        let err = repo.update(model.clone()).unwrap_err();
+        assert_eq!(err, RepositoryError::NotFound(model.key.to_string()));
+    }
+
+    #[test]
+    fn delete_list() {
+        let repo = MemoryRepository::new();
+        let keys: Vec<String> = (0..5)
+            .map(|n| repo.insert(format!("value {}", n)).unwrap().key.to_string())
+            .collect();
+
+        let list = repo.list().unwrap();
+        assert_eq!(list.len(), 5);
+
+        let deleted = repo.delete(&keys[0]).unwrap().expect("should delete");
+        assert_eq!(deleted.key, keys[0]);
+        assert!(repo.delete(&keys[0]).unwrap().is_none());
+        assert_eq!(repo.list().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn soft_delete_restore() {
+        let repo = MemoryRepository::new();
+        let keys: Vec<String> = (0..3)
+            .map(|n| repo.insert(format!("value {}", n)).unwrap().key.to_string())
+            .collect();
+
+        let deleted = repo.soft_delete(&keys[1]).unwrap();
+        assert!(deleted.is_deleted());
+        assert_eq!(deleted.version.update_count, 1);
+
+        // deleting again is a no-op
+        assert_eq!(repo.soft_delete(&keys[1]).unwrap(), deleted);
+
+        let list = repo.list().unwrap();
+        assert_eq!(list.len(), 2);
+        assert!(list.iter().all(|model| model.key != keys[1]));
+        assert_eq!(repo.list_all().unwrap().len(), 3);
+        assert_eq!(repo.get(&keys[1]).unwrap(), Some(deleted));
+
+        let restored = repo
+            .restore(&keys[1], Status::Inactive(StatusFlags::NONE))
+            .unwrap();
+        assert_eq!(restored.deleted_at, None);
+        assert_eq!(restored.version.update_count, 2);
+        assert_eq!(repo.list().unwrap().len(), 3);
+
+        match repo.restore(&keys[1], Status::Active(StatusFlags::NONE)) {
+            Err(RepositoryError::Transition(_)) => (),
+            other => panic!("should not restore: {:?}", other),
+        }
+        assert!(matches!(
+            repo.soft_delete("missing"),
+            Err(RepositoryError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn update_transitions() {
+        let repo = MemoryRepository::new();
+        let model = repo.insert("my value".to_string()).unwrap();
+
+        // New -> Processed isn't in the default table
+        let mut processed = model.clone();
+        processed.status = Status::Processed(StatusFlags::NONE);
+        match repo.update(processed) {
+            Err(RepositoryError::Transition(err)) => {
+                assert_eq!(err.from, StatusKind::New);
+                assert_eq!(err.to, StatusKind::Processed);
+            }
+            other => panic!("should not transition: {:?}", other),
+        }
+
+        // deleted_at follows the status, whatever the caller sent
+        let mut deleted = model.clone();
+        deleted.status = Status::Deleted(StatusFlags::NONE);
+        deleted.deleted_at = None;
+        let deleted = repo.update(deleted).unwrap();
+        assert!(deleted.deleted_at.is_some());
+
+        let mut tampered = deleted.clone();
+        tampered.deleted_at = None;
+        assert_eq!(repo.update(tampered).unwrap(), deleted);
+
+        // leaving Deleted is a restore and clears deleted_at
+        let mut active = deleted.clone();
+        active.status = Status::Active(StatusFlags::NONE);
+        let active = repo.update(active).unwrap();
+        assert_eq!(active.deleted_at, None);
+
+        let mut stamped = active.clone();
+        stamped.deleted_at = deleted.deleted_at;
+        stamped.value = "new value".to_string();
+        assert_eq!(repo.update(stamped).unwrap().deleted_at, None);
+    }
+
+    #[test]
+    fn final_deletes() {
+        let repo =
+            MemoryRepository::new().with_transitions(Transitions::default().without_restore());
+        let model = repo.insert("my value".to_string()).unwrap();
+        let deleted = repo.soft_delete(&model.key).unwrap();
+
+        match repo.restore(&model.key, Status::Active(StatusFlags::NONE)) {
+            Err(RepositoryError::Transition(err)) => assert_eq!(err.from, StatusKind::Deleted),
+            other => panic!("should not restore: {:?}", other),
+        }
+        assert_eq!(repo.get(&model.key).unwrap(), Some(deleted.clone()));
+
+        // a plain update can't leave Deleted either
+        let mut active = deleted;
+        active.status = Status::Active(StatusFlags::NONE);
+        active.deleted_at = None;
+        assert!(matches!(
+            repo.update(active),
+            Err(RepositoryError::Transition(_))
+        ));
+    }
+
+    struct FailingSink;
+
+    impl EventSink for FailingSink {
+        fn emit(&self, _: &ModelEvent) -> std::io::Result<()> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+        }
+    }
+
+    #[test]
+    fn emit_events() {
+        let sink = Arc::new(MemorySink::new());
+        let repo = MemoryRepository::new().with_sink(sink.clone());
+
+        let model = repo.insert("my value".to_string()).unwrap();
+
+        // no-op updates don't emit
+        repo.update(model.clone()).unwrap();
+        assert_eq!(sink.events().len(), 1);
+
+        let mut changed = model.clone();
+        changed.value = "new value".to_string();
+        repo.update(changed).unwrap();
+        repo.soft_delete(&model.key).unwrap();
+
+        let events = sink.events();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].old_version, None);
+        assert_eq!(events[1].changes[0].old, Some("my value".into()));
+        assert_eq!(events[1].changes[0].new, Some("new value".into()));
+        assert_eq!(events[1].new_version.as_ref().unwrap().update_count, 1);
+        assert!(events[2].changes.is_empty());
+        assert_eq!(
+            events[2].new_status,
+            Some(Status::Deleted(StatusFlags::NONE))
+        );
+    }
+
+    #[test]
+    fn sink_error() {
+        let repo = MemoryRepository::new().with_sink(Arc::new(FailingSink));
+
+        match repo.insert("my value".to_string()) {
+            Err(RepositoryError::Store(msg)) => assert_eq!(msg, "event sink: disk full"),
+            other => panic!("should fail: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn purge_deleted() {
+        let repo = MemoryRepository::new();
+        let keep = repo.insert("keep".to_string()).unwrap();
+        let old = repo.insert("old".to_string()).unwrap();
+        repo.soft_delete(&old.key).unwrap();
+
+        let policy = PurgePolicy::new(Duration::days(7));
+        let now = Utc::now().naive_utc();
+
+        assert!(repo.purge(&policy, now).unwrap().is_empty());
+        assert_eq!(repo.len(), 2);
+
+        let purged = repo.purge(&policy, now + Duration::days(8)).unwrap();
+        assert_eq!(purged.len(), 1);
+        assert_eq!(purged[0].key, old.key);
+        assert_eq!(repo.len(), 1);
+        assert!(repo.get(&keep.key).unwrap().is_some());
+    }
+
+    #[test]
+    fn validator() {
+        let repo = MemoryRepository::new().with_validator(no_empty_strings);
+
+        let err = repo.insert(String::new()).unwrap_err();
+        assert_eq!(
+            err,
+            RepositoryError::Invalid(vec![ModelError::Email(Cow::from(""))])
+        );
+        assert!(repo.is_empty());
+
+        let mut model = repo.insert("ok".to_string()).unwrap();
+        model.value = String::new();
+        assert!(matches!(
+            repo.update(model),
+            Err(RepositoryError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn error_display() {
+        let err = RepositoryError::NotFound("abc".to_string());
+        assert_eq!(err.to_string(), "model not found for key: abc");
+
+        let err = RepositoryError::Invalid(vec![ModelError::Email(Cow::from("bad"))]);
+        assert_eq!(err.to_string(), "invalid model: invalid email: \"bad\"");
+
+        let err = RepositoryError::from(TransitionError {
+            from: StatusKind::Active,
+            to: StatusKind::Deleted,
+        });
+        assert_eq!(
+            err.to_string(),
+            "illegal status transition: Active -> Deleted"
+        );
+
+        let err = RepositoryError::CrossTenant("abc".to_string());
+        assert_eq!(
+            err.to_string(),
+            "model belongs to another tenant for key: abc"
+        );
+    }
+
+    #[test]
+    fn unhashable_values() {
+        // maps with tuple keys serialize, but not to json
+        let repo: MemoryRepository<HashMap<(u8, u8), String>> = MemoryRepository::new();
+
+        let mut points = HashMap::new();
+        points.insert((1, 2), "a".to_string());
+        assert!(matches!(
+            repo.insert(points.clone()),
+            Err(RepositoryError::Serialize(_))
+        ));
+
+        let model = repo.insert(HashMap::new()).unwrap();
+        let mut changed = model.clone();
+        changed.value = points;
+        let err = repo.update(changed).unwrap_err();
+        assert!(err.to_string().starts_with("serialize error: "));
+        assert_eq!(repo.get(&model.key).unwrap(), Some(model));
+    }
+
+    #[test]
+    fn expiry() {
+        let sink = Arc::new(MemorySink::new());
+        let repo = MemoryRepository::new()
+            .with_ttl(Duration::minutes(5))
+            .with_sink(sink.clone());
+
+        let live = repo.insert("live".to_string()).unwrap();
+        let now = Utc::now().naive_utc();
+        assert!(live.expires_at.unwrap() > now);
+
+        // expire a model through an update
+        let mut expired = repo.insert("expired".to_string()).unwrap();
+        expired.expires_at = Some(now - Duration::seconds(1));
+        let expired = repo.update(expired).unwrap();
+
+        assert_eq!(repo.len(), 1);
+        assert!(repo.get(&expired.key).unwrap().is_none());
+        assert_eq!(repo.list_all().unwrap(), vec![live.clone()]);
+        assert_eq!(
+            repo.update(expired.clone()),
+            Err(RepositoryError::NotFound(expired.key.to_string()))
+        );
+
+        // an extended model is unchanged in value but a new write
+        let extended = Model {
+            version: live.version.clone(),
+            ..live.extend(Duration::minutes(5))
+        };
+        let extended = repo.update(extended).unwrap();
+        assert_eq!(extended.version.update_count, 1);
+        assert!(extended.expires_at > live.expires_at);
+
+        assert_eq!(repo.purge_expired(Utc::now().naive_utc()).unwrap(), 1);
+        assert!(repo.delete(&expired.key).unwrap().is_none());
+        assert_eq!(sink.events_for(&expired.key).len(), 3);
+
+        // expired models are purged and removed as of the time given
+        let later = now + Duration::minutes(20);
+        assert_eq!(repo.purge_expired(later).unwrap(), 1);
+        assert!(repo.is_empty());
+    }
+
+    #[test]
+    fn tenant_isolation() {
+        let sink = Arc::new(MemorySink::new());
+        let store = MemoryRepository::new().with_sink(sink.clone());
+        let acme = store.for_tenant(TenantId::new("acme").unwrap());
+        let globex = store.for_tenant(TenantId::new("globex").unwrap());
+
+        let a = acme.insert("a".to_string()).unwrap();
+        let g = globex.insert("g".to_string()).unwrap();
+        let shared = store.insert("shared".to_string()).unwrap();
+        assert_eq!(a.tenant, acme.tenant().cloned());
+        assert_eq!(shared.tenant, None);
+
+        assert_eq!(store.len(), 3);
+        assert_eq!(acme.len(), 1);
+        assert_eq!(globex.list_all().unwrap(), vec![g.clone()]);
+
+        let cross = |err: RepositoryError| err == RepositoryError::CrossTenant(a.key.to_string());
+        assert!(cross(globex.get(&a.key).unwrap_err()));
+        assert!(cross(globex.delete(&a.key).unwrap_err()));
+        assert!(cross(globex.soft_delete(&a.key).unwrap_err()));
+        assert_eq!(
+            acme.get(&shared.key).unwrap_err(),
+            RepositoryError::CrossTenant(shared.key.to_string())
+        );
+
+        // a model can't be moved to another tenant
+        let mut moved = a.clone();
+        moved.tenant = globex.tenant().cloned();
+        moved.value = "moved".to_string();
+        assert!(cross(acme.update(moved.clone()).unwrap_err()));
+        assert!(cross(globex.update(moved).unwrap_err()));
+
+        let mut changed = a.clone();
+        changed.value = "changed".to_string();
+        let updated = acme.update(changed).unwrap();
+        assert_eq!(updated.tenant, a.tenant);
+
+        // the unscoped store sees every tenant
+        assert_eq!(store.get(&g.key).unwrap(), Some(g));
+        assert!(acme.get("no-such-key").unwrap().is_none());
+
+        let events = sink.events_for(&a.key);
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|event| event.tenant == a.tenant));
+    }
+}
@@ -0,0 +1,404 @@
+//!
+//! Schema versioning for stored models.  Stored json outlives the struct that wrote it, so every
+//! model records its value's schema version, see `Model::schema`, and a domain registers an
+//! upcaster for each old version.  An upcaster transforms the json of a value from its version
+//! to the next, e.g., v1 to v2; loading an old record runs the upcasters in order until the
+//! value is at the current version, then deserializes it into the current type.
+//!
+//! Upcast models keep their key, version and status; the hash is recalculated for the new value
+//! so the next `apply` of the same value is a no-op.  Write the model back to persist the
+//! upgrade.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::models::Model;
+//! use domain_keys::schema::Upcasters;
+//! use serde_derive::{Deserialize, Serialize};
+//! use serde_json::{json, Value};
+//!
+//! #[derive(Debug, Serialize, Deserialize)]
+//! struct Person {
+//!     first_name: String,
+//!     last_name: String,
+//! }
+//!
+//! // v1 stored a single name
+//! fn split_name(value: Value) -> Result<Value, String> {
+//!     let name = value["name"].as_str().ok_or("name is missing")?;
+//!     let (first, last) = name.split_once(' ').unwrap_or((name, ""));
+//!
+//!     Ok(json!({ "first_name": first, "last_name": last }))
+//! }
+//!
+//! let upcasters = Upcasters::new(2).with_upcaster(1, split_name);
+//!
+//! let old = Model::new(json!({ "name": "sam smith" }));
+//! let json = serde_json::to_string(&old).unwrap();
+//!
+//! let model: Model<Person> = upcasters.from_json(&json).unwrap();
+//! assert_eq!(model.schema, 2);
+//! assert_eq!(model.key, old.key);
+//! assert_eq!(model.value.first_name, "sam");
+//! assert_eq!(model.value.last_name, "smith");
+//! ```
+//!
+
+use crate::models::{DomainModel, Model, FIRST_SCHEMA};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Transform a value's json from its schema version to the next; the error is the reason.
+pub type Upcaster = fn(Value) -> Result<Value, String>;
+
+/// Schema errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SchemaError {
+    /// no upcaster is registered for the version
+    MissingUpcaster(u16),
+    /// the model was written by a newer release than this one
+    Newer { found: u16, current: u16 },
+    /// the upcaster from the version failed
+    Upcast { from: u16, reason: String },
+    /// the json is not a model, or the upcast value doesn't match the current type
+    Json(String),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::MissingUpcaster(from) => {
+                write!(f, "no upcaster from schema version: {}", from)
+            }
+            SchemaError::Newer { found, current } => write!(
+                f,
+                "schema version {} is newer than the current version {}",
+                found, current
+            ),
+            SchemaError::Upcast { from, reason } => {
+                write!(f, "upcast from schema version {} failed: {}", from, reason)
+            }
+            SchemaError::Json(msg) => write!(f, "json error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<serde_json::Error> for SchemaError {
+    fn from(err: serde_json::Error) -> Self {
+        SchemaError::Json(err.to_string())
+    }
+}
+
+/// The upcasters for a domain and its current schema version.
+#[derive(Debug, Clone)]
+pub struct Upcasters {
+    current: u16,
+    upcasters: BTreeMap<u16, Upcaster>,
+}
+
+impl Upcasters {
+    /// Create the registry for values at the current schema version.
+    pub fn new(current: u16) -> Upcasters {
+        Upcasters {
+            current,
+            upcasters: BTreeMap::new(),
+        }
+    }
+
+    /// Create the registry for the domain's schema version, `DomainModel::SCHEMA`.
+    pub fn for_domain<T: DomainModel>() -> Upcasters {
+        Upcasters::new(T::SCHEMA)
+    }
+
+    /// Register the upcaster from the version to the next; replaces any upcaster for the
+    /// version.
+    pub fn with_upcaster(mut self, from: u16, upcaster: Upcaster) -> Upcasters {
+        self.upcasters.insert(from, upcaster);
+        self
+    }
+
+    /// Return the current schema version.
+    pub fn current(&self) -> u16 {
+        self.current
+    }
+
+    /// Upcast the model's json in place to the current version and return the version it was
+    /// stored at.  A model without a schema version is at the first version.  Nothing changes
+    /// if an upcaster fails.
+    pub fn upcast(&self, doc: &mut Value) -> Result<u16, SchemaError> {
+        let found = match doc.get("schema") {
+            None => FIRST_SCHEMA,
+            Some(schema) => schema
+                .as_u64()
+                .and_then(|schema| u16::try_from(schema).ok())
+                .ok_or_else(|| SchemaError::Json(format!("invalid schema version: {}", schema)))?,
+        };
+
+        if found > self.current {
+            return Err(SchemaError::Newer {
+                found,
+                current: self.current,
+            });
+        }
+
+        if found == self.current {
+            return Ok(found);
+        }
+
+        let mut value = match doc.get("value") {
+            Some(value) => value.clone(),
+            None => return Err(SchemaError::Json("missing field `value`".to_string())),
+        };
+
+        for from in found..self.current {
+            let upcaster = self
+                .upcasters
+                .get(&from)
+                .ok_or(SchemaError::MissingUpcaster(from))?;
+
+            value = upcaster(value).map_err(|reason| SchemaError::Upcast { from, reason })?;
+        }
+
+        doc["value"] = value;
+        doc["schema"] = Value::from(self.current);
+
+        Ok(found)
+    }
+
+    /// Upcast the model's json and deserialize it at the current version; an upcast value is
+    /// re-hashed.
+    pub fn from_value<T, K>(&self, mut doc: Value) -> Result<Model<T, K>, SchemaError>
+    where
+        T: serde::Serialize + DeserializeOwned,
+        K: DeserializeOwned,
+    {
+        let found = self.upcast(&mut doc)?;
+
+        let mut model: Model<T, K> = serde_json::from_value(doc)?;
+        if found != self.current {
+            model.version.hash = Model::try_calc_hash(&model.value)?;
+        }
+
+        Ok(model)
+    }
+
+    /// Parse, upcast and deserialize the stored model json.
+    pub fn from_json<T, K>(&self, json: &str) -> Result<Model<T, K>, SchemaError>
+    where
+        T: serde::Serialize + DeserializeOwned,
+        K: DeserializeOwned,
+    {
+        self.from_value(serde_json::from_str(json)?)
+    }
+
+    /// Parse, upcast and deserialize the stored model json bytes.
+    pub fn from_slice<T, K>(&self, json: &[u8]) -> Result<Model<T, K>, SchemaError>
+    where
+        T: serde::Serialize + DeserializeOwned,
+        K: DeserializeOwned,
+    {
+        self.from_value(serde_json::from_slice(json)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::RouteId;
+    use crate::models::{Applied, ModelError, Status, StatusFlags};
+    use serde_derive::{Deserialize, Serialize};
+    use serde_json::json;
+
+    // v3: the name is split and the email is a list
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Person {
+        first_name: String,
+        last_name: String,
+        emails: Vec<String>,
+    }
+
+    impl DomainModel for Person {
+        type Key = RouteId;
+
+        const SCHEMA: u16 = 3;
+
+        fn validate(_: &Model<Self>) -> Vec<ModelError> {
+            vec![]
+        }
+    }
+
+    fn split_name(mut value: Value) -> Result<Value, String> {
+        let name = value["name"].as_str().ok_or("name is missing")?.to_string();
+        let (first, last) = name.split_once(' ').unwrap_or((&name, ""));
+
+        let map = value.as_object_mut().ok_or("not an object")?;
+        map.remove("name");
+        map.insert("first_name".to_string(), json!(first));
+        map.insert("last_name".to_string(), json!(last));
+
+        Ok(value)
+    }
+
+    fn email_list(mut value: Value) -> Result<Value, String> {
+        let map = value.as_object_mut().ok_or("not an object")?;
+        let email = map.remove("email").unwrap_or(Value::Null);
+        let emails = match email {
+            Value::Null => json!([]),
+            email => json!([email]),
+        };
+        map.insert("emails".to_string(), emails);
+
+        Ok(value)
+    }
+
+    fn upcasters() -> Upcasters {
+        Upcasters::for_domain::<Person>()
+            .with_upcaster(1, split_name)
+            .with_upcaster(2, email_list)
+    }
+
+    fn v1() -> Model<Value> {
+        Model::new(json!({ "name": "sam smith", "email": "sam@rcs.com" }))
+    }
+
+    fn person() -> Person {
+        Person {
+            first_name: "sam".to_string(),
+            last_name: "smith".to_string(),
+            emails: vec!["sam@rcs.com".to_string()],
+        }
+    }
+
+    #[test]
+    fn upcast_chain() {
+        let upcasters = upcasters();
+        assert_eq!(upcasters.current(), 3);
+
+        let old = v1().transition_with(Status::Active(StatusFlags::NONE), &Default::default());
+        let old = old.unwrap();
+        let json = serde_json::to_string(&old).unwrap();
+
+        let model: Model<Person> = upcasters.from_json(&json).unwrap();
+        assert_eq!(model.schema, 3);
+        assert_eq!(model.key, old.key);
+        assert_eq!(model.status, old.status);
+        assert_eq!(model.version.update_count, 1);
+        assert_eq!(model.value, person());
+
+        // the upcast value is re-hashed, so applying it again is a no-op
+        assert_eq!(model.version.hash, Model::calc_hash(&person()));
+        assert_eq!(model.apply(person()), Applied::Unchanged);
+
+        // from v2, only the last upcaster runs
+        let v2 = v1().with_schema(2);
+        let mut doc = serde_json::to_value(&v2).unwrap();
+        doc["value"] = split_name(doc["value"].clone()).unwrap();
+        let model: Model<Person> = upcasters.from_value(doc).unwrap();
+        assert_eq!(model.value, person());
+    }
+
+    #[test]
+    fn legacy_records() {
+        // stored before the schema version was tracked
+        let mut doc = serde_json::to_value(v1()).unwrap();
+        doc.as_object_mut().unwrap().remove("schema");
+
+        let mut upcast = doc.clone();
+        assert_eq!(upcasters().upcast(&mut upcast), Ok(FIRST_SCHEMA));
+        assert_eq!(upcast["schema"], 3);
+        assert_eq!(upcast["value"]["emails"], json!(["sam@rcs.com"]));
+
+        let bytes = serde_json::to_vec(&doc).unwrap();
+        let model: Model<Person> = upcasters().from_slice(&bytes).unwrap();
+        assert_eq!(model.value, person());
+    }
+
+    #[test]
+    fn current_models() {
+        let model = Model::new_domain(person());
+        assert_eq!(model.schema, 3);
+
+        let json = serde_json::to_string(&model).unwrap();
+        let loaded: Model<Person> = Upcasters::for_domain::<Person>().from_json(&json).unwrap();
+        assert_eq!(loaded, model);
+    }
+
+    #[test]
+    fn repository_round_trip() {
+        use crate::repository::{MemoryRepository, Repository};
+
+        let repo = MemoryRepository::for_domain();
+        let model = repo.insert(person()).unwrap();
+        assert_eq!(model.schema, 3);
+
+        let mut changed = model.clone();
+        changed.value.emails.push("sam@smith.com".to_string());
+        let updated = repo.update(changed).unwrap();
+        assert_eq!(updated.version.update_count, 1);
+        assert_eq!(updated.schema, 3);
+
+        // current records load as is; the upcasters don't run
+        let json = serde_json::to_string(&repo.get(&model.key).unwrap().unwrap()).unwrap();
+        let loaded: Model<Person> = upcasters().from_json(&json).unwrap();
+        assert_eq!(loaded, updated);
+
+        // a schema change alone is a write
+        let older = repo.update(updated.with_schema(2)).unwrap();
+        assert_eq!(older.schema, 2);
+        assert_eq!(older.version.update_count, 2);
+    }
+
+    #[test]
+    fn errors() {
+        let json = serde_json::to_string(&v1()).unwrap();
+
+        let missing = Upcasters::new(3).with_upcaster(1, split_name);
+        assert_eq!(
+            missing.from_json::<Person, RouteId>(&json),
+            Err(SchemaError::MissingUpcaster(2))
+        );
+
+        let newer = serde_json::to_string(&v1().with_schema(4)).unwrap();
+        assert_eq!(
+            upcasters().from_json::<Person, RouteId>(&newer),
+            Err(SchemaError::Newer {
+                found: 4,
+                current: 3
+            })
+        );
+
+        let bad = serde_json::to_string(&Model::new(json!({ "email": "sam@rcs.com" }))).unwrap();
+        let err = upcasters().from_json::<Person, RouteId>(&bad).unwrap_err();
+        assert_eq!(
+            err,
+            SchemaError::Upcast {
+                from: 1,
+                reason: "name is missing".to_string()
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "upcast from schema version 1 failed: name is missing"
+        );
+
+        // the upcast value must match the current type
+        let wrong = Upcasters::new(3)
+            .with_upcaster(1, email_list)
+            .with_upcaster(2, email_list);
+        assert!(matches!(
+            wrong.from_json::<Person, RouteId>(&json),
+            Err(SchemaError::Json(_))
+        ));
+
+        let mut doc = serde_json::to_value(v1()).unwrap();
+        doc["schema"] = json!("two");
+        assert!(matches!(
+            upcasters().upcast(&mut doc),
+            Err(SchemaError::Json(_))
+        ));
+    }
+}
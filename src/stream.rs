@@ -0,0 +1,323 @@
+//!
+//! Stream conversion of newline-delimited values, used by the `base62 --stream` cli.  Each line is
+//! encoded or decoded on its own; bad values are reported to the error writer so a single bad
+//! value doesn't abort a pipeline of millions.  Every input line writes exactly one output line,
+//! so output row n always matches input row n.
+//!
+//! Lines may be simple CSV records (no quoted fields); when a column is selected only that field
+//! is converted and the other fields pass through unchanged.
+//!
+
+use crate::codec::{Codec, BASE62};
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+/// Encode numbers to strings or decode strings to numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Encode,
+    Decode,
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "encode" | "e" => Ok(Direction::Encode),
+            "decode" | "d" => Ok(Direction::Decode),
+            _ => Err(format!("unknown direction: {}; use encode or decode", s)),
+        }
+    }
+}
+
+/// Stream settings; column is 1-based, like `cut -f`.
+#[derive(Debug, Clone)]
+pub struct StreamConfig {
+    pub direction: Direction,
+    pub codec: Codec,
+    pub column: Option<usize>,
+    pub delimiter: char,
+    pub header: bool,
+}
+
+impl StreamConfig {
+    /// Create a new stream config for the direction with base62, no column and a comma delimiter.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::stream::{Direction, StreamConfig};
+    ///
+    /// let config = StreamConfig::new(Direction::Decode);
+    ///
+    /// assert_eq!(config.column, None);
+    /// assert_eq!(config.delimiter, ',');
+    /// ```
+    pub fn new(direction: Direction) -> StreamConfig {
+        StreamConfig {
+            direction,
+            codec: BASE62,
+            column: None,
+            delimiter: ',',
+            header: false,
+        }
+    }
+}
+
+/// Line counts for a converted stream.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct StreamSummary {
+    pub lines: usize,
+    pub converted: usize,
+    pub errors: usize,
+}
+
+impl StreamSummary {
+    /// Add the counts from another stream, e.g., the next input file.
+    pub fn add(&mut self, other: StreamSummary) {
+        self.lines += other.lines;
+        self.converted += other.converted;
+        self.errors += other.errors;
+    }
+}
+
+/// Convert each line from the reader and write it to out.  Errors, including lines that aren't
+/// utf-8, are written to err as `source:line: message` and the value is written as an empty
+/// field, or the row is passed through unchanged if the column is missing, so the output stays
+/// aligned with the input.  Only read and write errors abort the stream.  Blank lines are
+/// written as blank lines.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::stream::{convert_stream, Direction, StreamConfig};
+///
+/// let config = StreamConfig::new(Direction::Encode);
+/// let input = "1000\nbad\n1000000\n";
+/// let mut out = Vec::new();
+/// let mut err = Vec::new();
+///
+/// let summary = convert_stream(&config, "stdin", input.as_bytes(), &mut out, &mut err).unwrap();
+///
+/// assert_eq!(String::from_utf8(out).unwrap(), "G8\n\n4C92\n");
+/// assert!(String::from_utf8(err).unwrap().starts_with("stdin:2:"));
+/// assert_eq!(summary.converted, 2);
+/// assert_eq!(summary.errors, 1);
+/// ```
+pub fn convert_stream<R: BufRead, W: Write, E: Write>(
+    config: &StreamConfig,
+    source: &str,
+    mut reader: R,
+    out: &mut W,
+    err: &mut E,
+) -> std::io::Result<StreamSummary> {
+    let mut summary = StreamSummary::default();
+    let mut buf = Vec::new();
+
+    // read raw lines so a line that isn't utf-8 is reported like any other bad line
+    for idx in 0.. {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+
+        let line = match std::str::from_utf8(&buf) {
+            Ok(line) => line.trim_end_matches('\n').trim_end_matches('\r'),
+            Err(e) => {
+                summary.lines += 1;
+                summary.errors += 1;
+                writeln!(err, "{}:{}: invalid utf-8: {}", source, idx + 1, e)?;
+                writeln!(out)?;
+                continue;
+            }
+        };
+
+        if idx == 0 && config.header {
+            writeln!(out, "{}", line)?;
+            continue;
+        }
+
+        if line.trim().is_empty() {
+            writeln!(out)?;
+            continue;
+        }
+
+        summary.lines += 1;
+        let (converted, error) = convert_line(config, line);
+        match error {
+            Some(msg) => {
+                summary.errors += 1;
+                writeln!(err, "{}:{}: {}", source, idx + 1, msg)?;
+            }
+            None => summary.converted += 1,
+        }
+        writeln!(out, "{}", converted)?;
+    }
+
+    out.flush()?;
+
+    Ok(summary)
+}
+
+// convert the whole line or just the selected column and return the output row with the error,
+// if any; a bad value is written as an empty field and a row without the column passes through
+fn convert_line(config: &StreamConfig, line: &str) -> (String, Option<String>) {
+    let column = match config.column {
+        Some(column) => column,
+        None => {
+            return match convert_value(config, line) {
+                Ok(converted) => (converted, None),
+                Err(msg) => (String::new(), Some(msg)),
+            }
+        }
+    };
+
+    let mut fields: Vec<&str> = line.split(config.delimiter).collect();
+    let field = match column.checked_sub(1).and_then(|idx| fields.get(idx)) {
+        Some(field) => *field,
+        None => return (line.to_string(), Some(format!("missing column {}", column))),
+    };
+
+    let (converted, error) = match convert_value(config, field) {
+        Ok(converted) => (converted, None),
+        Err(msg) => (String::new(), Some(msg)),
+    };
+    fields[column - 1] = &converted;
+
+    let mut delimiter = [0_u8; 4];
+    (
+        fields.join(config.delimiter.encode_utf8(&mut delimiter)),
+        error,
+    )
+}
+
+fn convert_value(config: &StreamConfig, value: &str) -> Result<String, String> {
+    let value = value.trim();
+
+    match config.direction {
+        Direction::Encode => match value.parse::<u64>() {
+            Ok(n) => Ok(config.codec.encode(n)),
+            Err(_) => Err(format!("invalid u64 number: {}", value)),
+        },
+        Direction::Decode => match config.codec.decode(value) {
+            Ok(n) => Ok(n.to_string()),
+            Err(e) => Err(format!(
+                "invalid {} string: {} {}",
+                config.codec.name(),
+                value,
+                e
+            )),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(config: &StreamConfig, input: &str) -> (String, String, StreamSummary) {
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+        let summary = convert_stream(config, "test", input.as_bytes(), &mut out, &mut err).unwrap();
+
+        (
+            String::from_utf8(out).unwrap(),
+            String::from_utf8(err).unwrap(),
+            summary,
+        )
+    }
+
+    #[test]
+    fn direction_from_str() {
+        assert_eq!("encode".parse::<Direction>().unwrap(), Direction::Encode);
+        assert_eq!("d".parse::<Direction>().unwrap(), Direction::Decode);
+        assert!("sideways".parse::<Direction>().is_err());
+    }
+
+    #[test]
+    fn decode_lines() {
+        let config = StreamConfig::new(Direction::Decode);
+        let (out, err, summary) = run(&config, "G8\r\n\n4C92\nAB~CY\nz\n");
+
+        assert_eq!(out, "1000\n\n1000000\n\n61\n");
+        assert!(err.starts_with("test:4: invalid base62 string: AB~CY"));
+        assert_eq!(summary.lines, 4);
+        assert_eq!(summary.converted, 3);
+        assert_eq!(summary.errors, 1);
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let config = StreamConfig::new(Direction::Decode);
+        let input = b"G8\nA\xff\xfeB\r\n4C92";
+        let mut out = Vec::new();
+        let mut err = Vec::new();
+
+        let summary = convert_stream(&config, "test", &input[..], &mut out, &mut err).unwrap();
+        let err = String::from_utf8(err).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "1000\n\n1000000\n");
+        assert!(err.starts_with("test:2: invalid utf-8"));
+        assert_eq!(err.lines().count(), 1);
+        assert_eq!(summary.lines, 3);
+        assert_eq!(summary.converted, 2);
+        assert_eq!(summary.errors, 1);
+    }
+
+    #[test]
+    fn encode_column() {
+        let mut config = StreamConfig::new(Direction::Encode);
+        config.column = Some(2);
+        config.header = true;
+
+        let (out, err, summary) = run(&config, "name,id\nfoo,1000\nbar,x\nbaz\n");
+
+        // rows stay aligned: the bad value is emptied and the short row passes through
+        assert_eq!(out, "name,id\nfoo,G8\nbar,\nbaz\n");
+        assert_eq!(err.lines().count(), 2);
+        assert!(err.contains("test:4: missing column 2"));
+        assert_eq!(summary.errors, 2);
+    }
+
+    #[test]
+    fn tab_delimiter() {
+        let mut config = StreamConfig::new(Direction::Decode);
+        config.column = Some(1);
+        config.delimiter = '\t';
+
+        let (out, _, _) = run(&config, "G8\tfoo\n");
+
+        assert_eq!(out, "1000\tfoo\n");
+    }
+
+    #[test]
+    fn column_zero() {
+        let mut config = StreamConfig::new(Direction::Decode);
+        config.column = Some(0);
+
+        let (out, err, _) = run(&config, "G8\n");
+
+        assert_eq!(out, "G8\n");
+        assert!(err.contains("missing column 0"));
+    }
+
+    #[test]
+    fn add_summary() {
+        let mut total = StreamSummary::default();
+        total.add(StreamSummary {
+            lines: 3,
+            converted: 2,
+            errors: 1,
+        });
+        total.add(StreamSummary {
+            lines: 1,
+            converted: 1,
+            errors: 0,
+        });
+
+        assert_eq!(total.lines, 4);
+        assert_eq!(total.converted, 3);
+        assert_eq!(total.errors, 1);
+    }
+}
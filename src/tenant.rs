@@ -0,0 +1,232 @@
+//!
+//! Tenants for multi-tenant stores.  A `TenantId` names the customer that owns a model; models
+//! carry an optional tenant and a repository scoped to a tenant, see
+//! `MemoryRepository::for_tenant`, only reads and writes that tenant's models.  Reading another
+//! tenant's model is an error rather than a miss, so isolation bugs fail loudly.
+//!
+//! Backends that share a keyspace across tenants, e.g., a redis cluster, can store models under
+//! the tenant scoped key, `tenant:key`, and route by tenant then key so a tenant's models can
+//! be placed on their own shards.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::keys::RouteKey;
+//! use domain_keys::tenant::TenantId;
+//!
+//! let tenant = TenantId::new("acme").unwrap();
+//! let key = RouteKey::create();
+//!
+//! let scoped = tenant.scoped_key(&key);
+//! assert_eq!(scoped, format!("acme:{}", key));
+//!
+//! let (owner, model_key) = TenantId::split_key(&scoped).unwrap();
+//! assert_eq!(owner, tenant);
+//! assert_eq!(model_key, key);
+//!
+//! // route by tenant, then by key
+//! let (shard, route) = tenant.parse_route(&key, 4, 16).unwrap();
+//! assert_eq!(shard, tenant.route(4));
+//! assert!(route < 16);
+//! ```
+//!
+
+use crate::hash::fnv1a64;
+use crate::keys::{DomainKeyError, RouteKey};
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+// separates the tenant from the model key in a scoped key
+const SEPARATOR: char = ':';
+const MAX_TENANT_SIZE: usize = 64;
+
+/// A validated tenant identifier: 1 to 64 ascii letters, digits or dashes.
+#[derive(
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde_derive::Serialize,
+    serde_derive::Deserialize,
+)]
+#[serde(try_from = "String", into = "String")]
+pub struct TenantId(String);
+
+impl TenantId {
+    /// Validate and create the tenant id.
+    pub fn new(tenant: &str) -> Result<TenantId, DomainKeyError> {
+        if tenant.is_empty() || tenant.len() > MAX_TENANT_SIZE {
+            return Err(DomainKeyError::InvalidSize);
+        }
+
+        if !tenant
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+        {
+            return Err(DomainKeyError::ParseError);
+        }
+
+        Ok(TenantId(tenant.to_string()))
+    }
+
+    /// Return the model key namespaced by this tenant, `tenant:key`.
+    pub fn scoped_key(&self, key: &str) -> String {
+        format!("{}{}{}", self.0, SEPARATOR, key)
+    }
+
+    /// Create a new routing key namespaced by this tenant.
+    pub fn create_key(&self) -> String {
+        self.scoped_key(&RouteKey::create())
+    }
+
+    /// Split a scoped key into the tenant and the model key.
+    pub fn split_key(scoped: &str) -> Result<(TenantId, &str), DomainKeyError> {
+        match scoped.split_once(SEPARATOR) {
+            Some((tenant, key)) if !key.is_empty() => Ok((TenantId::new(tenant)?, key)),
+            _ => Err(DomainKeyError::ParseError),
+        }
+    }
+
+    /// Return the tenant's route in the range 0..total_routes; total routes are clamped to
+    /// 1..=128 like `RouteKey::parse_route`.  The route is a stable hash of the tenant id, so
+    /// it's the same across processes and releases.
+    pub fn route(&self, total_routes: u8) -> u8 {
+        let troutes = total_routes.clamp(1, 128);
+
+        (fnv1a64(self.0.as_bytes()) % troutes as u64) as u8
+    }
+
+    /// Route by tenant then key: return the tenant's route and the key's route within it.
+    pub fn parse_route(
+        &self,
+        key: &str,
+        tenant_routes: u8,
+        key_routes: u8,
+    ) -> Result<(u8, u8), DomainKeyError> {
+        Ok((
+            self.route(tenant_routes),
+            RouteKey::parse_route(key, key_routes)?,
+        ))
+    }
+}
+
+impl fmt::Display for TenantId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Deref for TenantId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for TenantId {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for TenantId {
+    type Err = DomainKeyError;
+
+    fn from_str(tenant: &str) -> Result<Self, Self::Err> {
+        TenantId::new(tenant)
+    }
+}
+
+impl TryFrom<String> for TenantId {
+    type Error = DomainKeyError;
+
+    fn try_from(tenant: String) -> Result<Self, Self::Error> {
+        TenantId::new(&tenant)
+    }
+}
+
+impl From<TenantId> for String {
+    fn from(tenant: TenantId) -> String {
+        tenant.0
+    }
+}
+
+impl PartialEq<str> for TenantId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for TenantId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate() {
+        assert!(TenantId::new("acme-corp-42").is_ok());
+        assert_eq!(TenantId::new(""), Err(DomainKeyError::InvalidSize));
+        assert_eq!(
+            TenantId::new(&"a".repeat(65)),
+            Err(DomainKeyError::InvalidSize)
+        );
+        assert_eq!(TenantId::new("acme:corp"), Err(DomainKeyError::ParseError));
+        assert_eq!(TenantId::new("acme corp"), Err(DomainKeyError::ParseError));
+    }
+
+    #[test]
+    fn scoped_keys() {
+        let tenant = TenantId::new("acme").unwrap();
+        let scoped = tenant.create_key();
+        assert!(scoped.starts_with("acme:"));
+
+        let (owner, key) = TenantId::split_key(&scoped).unwrap();
+        assert_eq!(owner, tenant);
+        assert_eq!(key.len(), 16);
+
+        assert_eq!(
+            TenantId::split_key("no-tenant"),
+            Err(DomainKeyError::ParseError)
+        );
+        assert_eq!(
+            TenantId::split_key("acme:"),
+            Err(DomainKeyError::ParseError)
+        );
+        assert_eq!(
+            TenantId::split_key(":key"),
+            Err(DomainKeyError::InvalidSize)
+        );
+    }
+
+    #[test]
+    fn stable_routes() {
+        let tenant = TenantId::new("acme").unwrap();
+        assert_eq!(tenant.route(1), 0);
+        assert_eq!(tenant.route(200), tenant.route(128));
+        assert_eq!(tenant.route(16), (fnv1a64(b"acme") % 16) as u8);
+
+        assert_eq!(
+            tenant.parse_route("x", 4, 16),
+            Err(DomainKeyError::InvalidSize)
+        );
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let tenant = TenantId::new("acme").unwrap();
+        let json = serde_json::to_string(&tenant).unwrap();
+        assert_eq!(json, r#""acme""#);
+        assert_eq!(serde_json::from_str::<TenantId>(&json).unwrap(), tenant);
+        assert!(serde_json::from_str::<TenantId>(r#""acme:corp""#).is_err());
+    }
+}
@@ -0,0 +1,626 @@
+//!
+//! Composable validation for domain values.  A `Validation` collects `FieldError`s, each with a
+//! field path, an error code and a message, by checking field values against reusable `Rule`s.
+//! The resulting `ValidationReport` serializes to json so an http layer can return it as is.
+//!
+//! Field paths are json pointers relative to the value, e.g., `/email` or `/address/zip`, the
+//! same form used by [`crate::history`] and [`crate::patch`].
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::validation::{Email, Length, Range, Required, Validation};
+//!
+//! let name = "s";
+//! let email = "sam@rcs.com";
+//! let age = 200_u8;
+//!
+//! let report = Validation::new()
+//!     .field("/name", name, &[&Required, &Length::new(2, 40)])
+//!     .field("/email", email, &[&Required, &Email])
+//!     .field("/age", &age, &[&Range::new(18, 120)])
+//!     .finish();
+//!
+//! assert!(!report.is_valid());
+//! assert_eq!(
+//!     serde_json::to_string(&report).unwrap(),
+//!     concat!(
+//!         r#"{"errors":[{"path":"/name","code":"length","message":"length must be between 2 and 40"},"#,
+//!         r#"{"path":"/age","code":"range","message":"must be between 18 and 120"}]}"#
+//!     )
+//! );
+//! ```
+//!
+
+use crate::models::ModelError;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+use serde_derive::{Deserialize, Serialize};
+use std::fmt;
+
+/// A failed rule for a field.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FieldError {
+    pub path: String,
+    pub code: String,
+    pub message: String,
+}
+
+impl FieldError {
+    /// Create a field error.
+    pub fn new(path: &str, code: &str, message: &str) -> FieldError {
+        FieldError {
+            path: path.to_string(),
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "{}: {}", self.path, self.message)
+        }
+    }
+}
+
+/// A rule violation without the field path; the path is added by `Validation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Violation {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Violation {
+    /// Create a violation.
+    pub fn new(code: &'static str, message: String) -> Violation {
+        Violation { code, message }
+    }
+}
+
+/// A reusable check for a field value.
+pub trait Rule<V: ?Sized> {
+    /// return the violation if the value breaks the rule
+    fn check(&self, value: &V) -> Option<Violation>;
+}
+
+/// The errors found by a validation, in the order they were found.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ValidationReport {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationReport {
+    /// Return true if there are no errors.
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Return the errors for the field path.
+    pub fn errors_for(&self, path: &str) -> Vec<&FieldError> {
+        self.errors.iter().filter(|e| e.path == path).collect()
+    }
+
+    /// Return Ok if there are no errors, else the report.
+    pub fn into_result(self) -> Result<(), ValidationReport> {
+        if self.is_valid() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Convert to model errors, e.g., to return from `DomainModel::validate`.
+    pub fn into_errors(self) -> Vec<ModelError> {
+        self.errors.into_iter().map(ModelError::Field).collect()
+    }
+}
+
+impl From<Vec<ModelError>> for ValidationReport {
+    fn from(errors: Vec<ModelError>) -> Self {
+        ValidationReport {
+            errors: errors.into_iter().map(FieldError::from).collect(),
+        }
+    }
+}
+
+impl From<ModelError> for FieldError {
+    fn from(err: ModelError) -> Self {
+        match err {
+            ModelError::Email(_) => FieldError::new("", "email", &err.to_string()),
+            ModelError::Field(err) => err,
+        }
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self.errors.iter().map(|e| e.to_string()).collect();
+        write!(f, "validation failed: {}", messages.join(", "))
+    }
+}
+
+impl std::error::Error for ValidationReport {}
+
+/// A validation in progress.  Every rule for a field is checked, so a field may report more than
+/// one error.
+#[derive(Debug, Default, Clone)]
+pub struct Validation {
+    report: ValidationReport,
+}
+
+impl Validation {
+    /// Create a new validation with no errors.
+    pub fn new() -> Validation {
+        Validation::default()
+    }
+
+    /// Check the field value against the rules.
+    pub fn field<V: ?Sized>(mut self, path: &str, value: &V, rules: &[&dyn Rule<V>]) -> Validation {
+        for rule in rules {
+            if let Some(violation) = rule.check(value) {
+                self.report.errors.push(FieldError {
+                    path: path.to_string(),
+                    code: violation.code.to_string(),
+                    message: violation.message,
+                });
+            }
+        }
+
+        self
+    }
+
+    /// Check an optional field value against the rules; a missing value is not checked.  Use
+    /// `Required` with `field` for values that must be present.
+    pub fn optional<V>(self, path: &str, value: Option<&V>, rules: &[&dyn Rule<V>]) -> Validation
+    where
+        V: ?Sized,
+    {
+        match value {
+            Some(value) => self.field(path, value, rules),
+            None => self,
+        }
+    }
+
+    /// Add an error for the path if the condition is false, for checks that aren't reusable,
+    /// e.g., comparing two fields.
+    pub fn check(mut self, path: &str, ok: bool, code: &str, message: &str) -> Validation {
+        if !ok {
+            self.report
+                .errors
+                .push(FieldError::new(path, code, message));
+        }
+
+        self
+    }
+
+    /// Add the errors from a nested value's report with their paths under the prefix.
+    pub fn nested(mut self, prefix: &str, report: ValidationReport) -> Validation {
+        for mut err in report.errors {
+            err.path = format!("{}{}", prefix, err.path);
+            self.report.errors.push(err);
+        }
+
+        self
+    }
+
+    /// Return the report.
+    pub fn finish(self) -> ValidationReport {
+        self.report
+    }
+}
+
+/// The value must be present: a string that isn't blank or an option that is some.
+#[derive(Debug, Clone, Copy)]
+pub struct Required;
+
+impl Rule<str> for Required {
+    fn check(&self, value: &str) -> Option<Violation> {
+        if value.trim().is_empty() {
+            Some(Violation::new("required", "is required".to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+impl<V> Rule<Option<V>> for Required {
+    fn check(&self, value: &Option<V>) -> Option<Violation> {
+        match value {
+            Some(_) => None,
+            None => Some(Violation::new("required", "is required".to_string())),
+        }
+    }
+}
+
+/// The string length in chars, or the list length, must be within the bounds.
+#[derive(Debug, Clone, Copy)]
+pub struct Length {
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+}
+
+impl Length {
+    /// Create a length rule for min..=max.
+    pub fn new(min: usize, max: usize) -> Length {
+        Length {
+            min: Some(min),
+            max: Some(max),
+        }
+    }
+
+    /// Create a length rule with only a minimum.
+    pub fn min(min: usize) -> Length {
+        Length {
+            min: Some(min),
+            max: None,
+        }
+    }
+
+    /// Create a length rule with only a maximum.
+    pub fn max(max: usize) -> Length {
+        Length {
+            min: None,
+            max: Some(max),
+        }
+    }
+
+    fn check_len(&self, len: usize) -> Option<Violation> {
+        let message = match (self.min, self.max) {
+            (Some(min), Some(max)) if len < min || len > max => {
+                format!("length must be between {} and {}", min, max)
+            }
+            (Some(min), None) if len < min => format!("length must be at least {}", min),
+            (None, Some(max)) if len > max => format!("length must be at most {}", max),
+            _ => return None,
+        };
+
+        Some(Violation::new("length", message))
+    }
+}
+
+impl Rule<str> for Length {
+    fn check(&self, value: &str) -> Option<Violation> {
+        self.check_len(value.chars().count())
+    }
+}
+
+impl<V> Rule<[V]> for Length {
+    fn check(&self, value: &[V]) -> Option<Violation> {
+        self.check_len(value.len())
+    }
+}
+
+/// The string must match the regular expression.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    regex: Regex,
+    message: String,
+}
+
+impl Pattern {
+    /// Create a pattern rule; returns an error if the regular expression is invalid.
+    ///
+    /// # Example:
+    ///
+    /// ```rust
+    /// use domain_keys::validation::{Pattern, Rule};
+    ///
+    /// let sku = Pattern::new("^[A-Z]{3}-[0-9]{4}$", "must be a sku, e.g., ABC-1234").unwrap();
+    ///
+    /// assert!(sku.check("ABC-1234").is_none());
+    /// assert_eq!(sku.check("abc").unwrap().code, "pattern");
+    /// ```
+    pub fn new(pattern: &str, message: &str) -> Result<Pattern, regex::Error> {
+        Ok(Pattern {
+            regex: Regex::new(pattern)?,
+            message: message.to_string(),
+        })
+    }
+}
+
+impl Rule<str> for Pattern {
+    fn check(&self, value: &str) -> Option<Violation> {
+        if self.regex.is_match(value) {
+            None
+        } else {
+            Some(Violation::new("pattern", self.message.clone()))
+        }
+    }
+}
+
+/// A `Pattern` that compiles on first use and is cached, so it can live in a static, e.g., the
+/// patterns generated by `#[derive(DomainModel)]`, which checks the regex when it expands.
+///
+/// # Example:
+///
+/// ```rust
+/// use domain_keys::validation::{LazyPattern, Rule};
+///
+/// static SKU: LazyPattern = LazyPattern::new("^[A-Z]{3}-[0-9]{4}$", "must be a sku");
+///
+/// assert!(SKU.check("ABC-1234").is_none());
+/// assert_eq!(SKU.check("abc").unwrap().message, "must be a sku");
+/// ```
+#[derive(Debug)]
+pub struct LazyPattern {
+    pattern: &'static str,
+    message: &'static str,
+    cell: OnceCell<Pattern>,
+}
+
+impl LazyPattern {
+    /// Create a lazy pattern rule; the regular expression isn't compiled until the first check.
+    pub const fn new(pattern: &'static str, message: &'static str) -> LazyPattern {
+        LazyPattern {
+            pattern,
+            message,
+            cell: OnceCell::new(),
+        }
+    }
+
+    /// Return the compiled pattern, compiling it on the first call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the regular expression is invalid.
+    pub fn get(&self) -> &Pattern {
+        self.cell
+            .get_or_init(|| match Pattern::new(self.pattern, self.message) {
+                Ok(pattern) => pattern,
+                Err(e) => panic!("invalid validation pattern {:?}: {}", self.pattern, e),
+            })
+    }
+}
+
+impl Rule<str> for LazyPattern {
+    fn check(&self, value: &str) -> Option<Violation> {
+        self.get().check(value)
+    }
+}
+
+/// The string must look like an email address: a local part and a dotted domain with no
+/// whitespace.  Blank values pass so optional emails can use the rule; add `Required` if the
+/// email must be present.
+#[derive(Debug, Clone, Copy)]
+pub struct Email;
+
+impl Rule<str> for Email {
+    fn check(&self, value: &str) -> Option<Violation> {
+        if value.is_empty() || is_email(value) {
+            None
+        } else {
+            Some(Violation::new("email", "invalid email".to_string()))
+        }
+    }
+}
+
+fn is_email(value: &str) -> bool {
+    let (local, domain) = match value.split_once('@') {
+        Some(parts) => parts,
+        None => return false,
+    };
+
+    let labels: Vec<&str> = domain.split('.').collect();
+
+    !local.is_empty()
+        && !value.chars().any(char::is_whitespace)
+        && !domain.contains('@')
+        && labels.len() > 1
+        && labels.iter().all(|label| !label.is_empty())
+}
+
+/// The string must be a phone number: 7 to 15 digits with an optional leading `+` and the
+/// separators space, `-`, `.` and parentheses.  Blank values pass, like `Email`.
+#[derive(Debug, Clone, Copy)]
+pub struct Phone;
+
+impl Rule<str> for Phone {
+    fn check(&self, value: &str) -> Option<Violation> {
+        if value.is_empty() || is_phone(value) {
+            None
+        } else {
+            Some(Violation::new("phone", "invalid phone number".to_string()))
+        }
+    }
+}
+
+fn is_phone(value: &str) -> bool {
+    let number = value.strip_prefix('+').unwrap_or(value);
+    let digits = number.chars().filter(|ch| ch.is_ascii_digit()).count();
+    let valid_chars = number
+        .chars()
+        .all(|ch| ch.is_ascii_digit() || " -.()".contains(ch));
+
+    valid_chars && (7..=15).contains(&digits)
+}
+
+/// The value must be within min..=max.
+#[derive(Debug, Clone, Copy)]
+pub struct Range<N> {
+    pub min: N,
+    pub max: N,
+}
+
+impl<N> Range<N> {
+    /// Create an inclusive range rule.
+    pub fn new(min: N, max: N) -> Range<N> {
+        Range { min, max }
+    }
+}
+
+impl<N: PartialOrd + fmt::Display> Rule<N> for Range<N> {
+    fn check(&self, value: &N) -> Option<Violation> {
+        if *value < self.min || *value > self.max {
+            let message = format!("must be between {} and {}", self.min, self.max);
+            Some(Violation::new("range", message))
+        } else {
+            None
+        }
+    }
+}
+
+/// The string must be one of the choices.
+#[derive(Debug, Clone, Copy)]
+pub struct OneOf<'a>(pub &'a [&'a str]);
+
+impl Rule<str> for OneOf<'_> {
+    fn check(&self, value: &str) -> Option<Violation> {
+        if self.0.contains(&value) {
+            None
+        } else {
+            let message = format!("must be one of: {}", self.0.join(", "));
+            Some(Violation::new("one_of", message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn codes(report: &ValidationReport) -> Vec<&str> {
+        report.errors.iter().map(|e| e.code.as_str()).collect()
+    }
+
+    #[test]
+    fn required() {
+        assert!(Required.check("sam").is_none());
+        assert!(Required.check("  ").is_some());
+        assert!(Required.check(&Some(1)).is_none());
+        assert!(Rule::<Option<u8>>::check(&Required, &None).is_some());
+    }
+
+    #[test]
+    fn length() {
+        assert!(Length::new(2, 4).check("ab").is_none());
+        assert!(Length::new(2, 4).check("ééé").is_none());
+        assert_eq!(
+            Length::new(2, 4).check("abcde").unwrap().message,
+            "length must be between 2 and 4"
+        );
+        assert_eq!(
+            Length::min(2).check("a").unwrap().message,
+            "length must be at least 2"
+        );
+        assert!(Length::max(2).check(&[1, 2, 3][..]).is_some());
+    }
+
+    #[test]
+    fn email() {
+        for good in ["sam@rcs.com", "first.last+tag@mail.example.org", ""] {
+            assert!(Email.check(good).is_none(), "{}", good);
+        }
+
+        for bad in [
+            "sam",
+            "@rcs.com",
+            "sam@rcs",
+            "sam@rcs.",
+            "sam @rcs.com",
+            "a@b@c.com",
+        ] {
+            assert!(Email.check(bad).is_some(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn phone() {
+        for good in ["+1 (206) 555-1234", "206.555.1234", "5551234"] {
+            assert!(Phone.check(good).is_none(), "{}", good);
+        }
+
+        for bad in ["555-123", "206-555-CALL", "+1 206 555 1234 5678 9"] {
+            assert!(Phone.check(bad).is_some(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn range_one_of() {
+        assert!(Range::new(1.0, 2.0).check(&1.5).is_none());
+        assert!(Range::new(1, 10).check(&0).is_some());
+        assert!(OneOf(&["a", "b"]).check("a").is_none());
+        assert_eq!(
+            OneOf(&["a", "b"]).check("c").unwrap().message,
+            "must be one of: a, b"
+        );
+    }
+
+    #[test]
+    fn compose() {
+        let address = Validation::new()
+            .field(
+                "/zip",
+                "9810",
+                &[&Pattern::new("^[0-9]{5}$", "bad zip").unwrap()],
+            )
+            .finish();
+
+        let status = "gone";
+        let report = Validation::new()
+            .field("/name", "", &[&Required, &Length::min(2)])
+            .optional("/phone", None::<&str>, &[&Phone])
+            .field("/status", status, &[&OneOf(&["active", "inactive"])])
+            .check("/end", 2 > 1, "order", "end must be after start")
+            .nested("/address", address)
+            .finish();
+
+        assert_eq!(
+            codes(&report),
+            vec!["required", "length", "one_of", "pattern"]
+        );
+        assert_eq!(report.errors_for("/address/zip")[0].message, "bad zip");
+        assert!(Validation::new().finish().into_result().is_ok());
+    }
+
+    #[test]
+    fn lazy_pattern() {
+        static ZIP: LazyPattern = LazyPattern::new("^[0-9]{5}$", "bad zip");
+
+        assert!(std::ptr::eq(ZIP.get(), ZIP.get()));
+        assert!(ZIP.check("98101").is_none());
+        assert_eq!(ZIP.check("9810").unwrap().code, "pattern");
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid validation pattern")]
+    fn lazy_pattern_invalid() {
+        LazyPattern::new("^[0-9]{5$", "bad zip").get();
+    }
+
+    #[test]
+    fn model_errors() {
+        let report = Validation::new()
+            .check("/email", false, "email", "invalid email")
+            .finish();
+
+        let errors = report.clone().into_errors();
+        assert_eq!(errors[0].to_string(), "/email: invalid email");
+        assert_eq!(ValidationReport::from(errors), report);
+
+        let legacy = ValidationReport::from(vec![ModelError::Email("bad".into())]);
+        assert_eq!(legacy.errors[0].code, "email");
+        assert_eq!(legacy.errors[0].path, "");
+    }
+
+    #[test]
+    fn report_serde() {
+        let report = Validation::new()
+            .check("/a", false, "custom", "bad a")
+            .finish();
+
+        let json = serde_json::to_string(&report).unwrap();
+        assert_eq!(
+            json,
+            r#"{"errors":[{"path":"/a","code":"custom","message":"bad a"}]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ValidationReport>(&json).unwrap(),
+            report
+        );
+        assert_eq!(report.to_string(), "validation failed: /a: bad a");
+    }
+}
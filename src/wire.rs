@@ -0,0 +1,434 @@
+//!
+//! Wire formats for stored models.  An encoded model is a four byte header followed by the
+//! model in one of the supported formats; the header identifies the format and the schema
+//! version of the value so readers can pick the decoder, and migrate old records, without
+//! guessing.
+//!
+//! | byte | contents                                   |
+//! |------|--------------------------------------------|
+//! | 0    | `MAGIC`, marks a domain_keys envelope      |
+//! | 1    | the format, see `Format`                   |
+//! | 2..4 | the model's schema version, u16 big endian |
+//!
+//! Json is always available.  The binary formats are behind features: `msgpack`, `cbor` and
+//! `bincode`; encoding or decoding a format that isn't enabled returns `WireError::Unsupported`.
+//! The binary formats write every model field, including empty options, so the positional
+//! formats (msgpack and bincode) round trip.  Values with `skip_serializing_if` fields should
+//! use json or cbor.
+//!
+//! Run `cargo run --release --example bench-formats --all-features` to compare sizes.
+//!
+//! # Example:
+//!
+//! ```rust
+//! use domain_keys::models::Model;
+//! use domain_keys::wire::{self, Format, Header};
+//!
+//! let model: Model<String> = Model::new("flarb".to_string()).with_schema(2);
+//!
+//! let bytes = wire::encode(&model, Format::Json).unwrap();
+//! assert_eq!(Header::read(&bytes).unwrap(), Header::new(Format::Json, 2));
+//!
+//! let (header, decoded) = wire::decode::<String, _>(&bytes).unwrap();
+//! assert_eq!(header.schema, 2);
+//! assert_eq!(decoded, model);
+//! ```
+//!
+
+use crate::models::Model;
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+use crate::models::{Status, StatusFlags, StatusKind, Version, TS};
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+use crate::tenant::TenantId;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+
+/// The first byte of every encoded model.
+pub const MAGIC: u8 = 0xd1;
+
+/// The header size in bytes.
+pub const HEADER_SIZE: usize = 4;
+
+/// The supported wire formats; the discriminant is the header's format byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Json = 1,
+    MessagePack = 2,
+    Cbor = 3,
+    Bincode = 4,
+}
+
+impl Format {
+    /// All formats, in header byte order.
+    pub const ALL: [Format; 4] = [
+        Format::Json,
+        Format::MessagePack,
+        Format::Cbor,
+        Format::Bincode,
+    ];
+
+    /// Return the format for the header byte.
+    pub fn from_byte(byte: u8) -> Result<Format, WireError> {
+        Format::ALL
+            .iter()
+            .find(|format| **format as u8 == byte)
+            .copied()
+            .ok_or(WireError::UnknownFormat(byte))
+    }
+
+    /// Return true if the format's feature is enabled in this build.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Format::Json => true,
+            Format::MessagePack => cfg!(feature = "msgpack"),
+            Format::Cbor => cfg!(feature = "cbor"),
+            Format::Bincode => cfg!(feature = "bincode"),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Json => "json",
+            Format::MessagePack => "msgpack",
+            Format::Cbor => "cbor",
+            Format::Bincode => "bincode",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// The envelope header: the format and the value's schema version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Header {
+    pub format: Format,
+    pub schema: u16,
+}
+
+impl Header {
+    pub fn new(format: Format, schema: u16) -> Header {
+        Header { format, schema }
+    }
+
+    /// Read the header from the start of an encoded model.
+    pub fn read(bytes: &[u8]) -> Result<Header, WireError> {
+        if bytes.len() < HEADER_SIZE || bytes[0] != MAGIC {
+            return Err(WireError::InvalidHeader);
+        }
+
+        Ok(Header {
+            format: Format::from_byte(bytes[1])?,
+            schema: u16::from_be_bytes([bytes[2], bytes[3]]),
+        })
+    }
+
+    /// Return the header bytes.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        let schema = self.schema.to_be_bytes();
+        [MAGIC, self.format as u8, schema[0], schema[1]]
+    }
+}
+
+/// Wire errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    /// the bytes don't start with a domain_keys header
+    InvalidHeader,
+    /// the header's format byte isn't a known format
+    UnknownFormat(u8),
+    /// the format's feature isn't enabled
+    Unsupported(Format),
+    /// the model could not be encoded
+    Encode(String),
+    /// the body is not a valid model in the header's format
+    Decode(String),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WireError::InvalidHeader => write!(f, "invalid wire header"),
+            WireError::UnknownFormat(byte) => write!(f, "unknown wire format: {}", byte),
+            WireError::Unsupported(format) => {
+                write!(f, "wire format not enabled: {}", format)
+            }
+            WireError::Encode(msg) => write!(f, "encode error: {}", msg),
+            WireError::Decode(msg) => write!(f, "decode error: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+// the binary layout: every field is written, so positional formats can read it back, and the
+// status is its kind's index and flags, since bincode can't read the json's tagged status
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+#[derive(serde_derive::Serialize)]
+struct WireRef<'a, T, K> {
+    key: &'a K,
+    version: &'a Version,
+    schema: u16,
+    status: (u8, StatusFlags),
+    value: &'a T,
+    deleted_at: &'a Option<TS>,
+    tenant: &'a Option<TenantId>,
+    expires_at: &'a Option<TS>,
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+#[derive(serde_derive::Deserialize)]
+struct WireModel<T, K> {
+    key: K,
+    version: Version,
+    schema: u16,
+    status: (u8, StatusFlags),
+    value: T,
+    deleted_at: Option<TS>,
+    tenant: Option<TenantId>,
+    expires_at: Option<TS>,
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+impl<'a, T, K> From<&'a Model<T, K>> for WireRef<'a, T, K> {
+    fn from(model: &'a Model<T, K>) -> Self {
+        WireRef {
+            key: &model.key,
+            version: &model.version,
+            schema: model.schema,
+            status: (model.status.kind() as u8, model.status.flags()),
+            value: &model.value,
+            deleted_at: &model.deleted_at,
+            tenant: &model.tenant,
+            expires_at: &model.expires_at,
+        }
+    }
+}
+
+#[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+impl<T, K> WireModel<T, K> {
+    fn into_model(self) -> Result<Model<T, K>, WireError> {
+        let (kind, flags) = self.status;
+        let kind = StatusKind::ALL
+            .get(kind as usize)
+            .ok_or_else(|| WireError::Decode(format!("invalid status kind: {}", kind)))?;
+
+        Ok(Model {
+            key: self.key,
+            version: self.version,
+            schema: self.schema,
+            status: Status::new(*kind, flags),
+            value: self.value,
+            deleted_at: self.deleted_at,
+            tenant: self.tenant,
+            expires_at: self.expires_at,
+        })
+    }
+}
+
+/// Encode the model with a header for the format and the model's schema version.
+pub fn encode<T: Serialize, K: Serialize>(
+    model: &Model<T, K>,
+    format: Format,
+) -> Result<Vec<u8>, WireError> {
+    let mut buf = Header::new(format, model.schema).to_bytes().to_vec();
+    encode_body(model, format, &mut buf)?;
+
+    Ok(buf)
+}
+
+/// Decode an encoded model, returning its header and the model.
+pub fn decode<T: DeserializeOwned, K: DeserializeOwned>(
+    bytes: &[u8],
+) -> Result<(Header, Model<T, K>), WireError> {
+    let header = Header::read(bytes)?;
+    let model = decode_body(&bytes[HEADER_SIZE..], header.format)?;
+
+    Ok((header, model))
+}
+
+fn encode_body<T: Serialize, K: Serialize>(
+    model: &Model<T, K>,
+    format: Format,
+    buf: &mut Vec<u8>,
+) -> Result<(), WireError> {
+    match format {
+        Format::Json => serde_json::to_writer(buf, model).map_err(encode_error),
+        #[cfg(feature = "msgpack")]
+        Format::MessagePack => {
+            rmp_serde::encode::write(buf, &WireRef::from(model)).map_err(encode_error)
+        }
+        #[cfg(feature = "cbor")]
+        Format::Cbor => {
+            ciborium::ser::into_writer(&WireRef::from(model), buf).map_err(encode_error)
+        }
+        #[cfg(feature = "bincode")]
+        Format::Bincode => {
+            bincode::serialize_into(buf, &WireRef::from(model)).map_err(encode_error)
+        }
+        #[allow(unreachable_patterns)]
+        _ => Err(WireError::Unsupported(format)),
+    }
+}
+
+fn decode_body<T: DeserializeOwned, K: DeserializeOwned>(
+    body: &[u8],
+    format: Format,
+) -> Result<Model<T, K>, WireError> {
+    match format {
+        Format::Json => serde_json::from_slice(body).map_err(decode_error),
+        #[cfg(feature = "msgpack")]
+        Format::MessagePack => rmp_serde::from_slice::<WireModel<T, K>>(body)
+            .map_err(decode_error)
+            .and_then(WireModel::into_model),
+        #[cfg(feature = "cbor")]
+        Format::Cbor => ciborium::de::from_reader::<WireModel<T, K>, _>(body)
+            .map_err(decode_error)
+            .and_then(WireModel::into_model),
+        #[cfg(feature = "bincode")]
+        Format::Bincode => bincode::deserialize::<WireModel<T, K>>(body)
+            .map_err(decode_error)
+            .and_then(WireModel::into_model),
+        #[allow(unreachable_patterns)]
+        _ => Err(WireError::Unsupported(format)),
+    }
+}
+
+fn encode_error<E: fmt::Display>(err: E) -> WireError {
+    WireError::Encode(err.to_string())
+}
+
+fn decode_error<E: fmt::Display>(err: E) -> WireError {
+    WireError::Decode(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::RouteId;
+    use serde_derive::{Deserialize, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Person {
+        name: String,
+        age: u8,
+        tags: Vec<String>,
+    }
+
+    fn person() -> Model<Person> {
+        Model::new(Person {
+            name: "sam".to_string(),
+            age: 42,
+            tags: vec!["a".to_string(), "b".to_string()],
+        })
+    }
+
+    #[test]
+    fn header() {
+        let header = Header::new(Format::Cbor, 258);
+        let bytes = header.to_bytes();
+        assert_eq!(bytes, [MAGIC, 3, 1, 2]);
+        assert_eq!(Header::read(&bytes), Ok(header));
+
+        assert_eq!(Header::read(&bytes[..3]), Err(WireError::InvalidHeader));
+        assert_eq!(Header::read(b"{\"k"), Err(WireError::InvalidHeader));
+        assert_eq!(
+            Header::read(&[MAGIC, 9, 0, 1]),
+            Err(WireError::UnknownFormat(9))
+        );
+
+        for format in Format::ALL {
+            assert_eq!(Format::from_byte(format as u8), Ok(format));
+        }
+    }
+
+    #[test]
+    fn json() {
+        let model = person();
+        let bytes = encode(&model, Format::Json).unwrap();
+        assert_eq!(&bytes[HEADER_SIZE..], serde_json::to_vec(&model).unwrap());
+
+        let (header, decoded) = decode::<Person, _>(&bytes).unwrap();
+        assert_eq!(header, Header::new(Format::Json, 1));
+        assert_eq!(decoded, model);
+
+        let mut bad = bytes.clone();
+        bad.truncate(HEADER_SIZE + 4);
+        assert!(matches!(
+            decode::<Person, RouteId>(&bad),
+            Err(WireError::Decode(_))
+        ));
+    }
+
+    #[cfg(any(feature = "msgpack", feature = "cbor", feature = "bincode"))]
+    fn round_trip(format: Format) {
+        use crate::keys::{DomainId, KeyDomain};
+
+        assert!(format.is_enabled());
+
+        let model = person().with_schema(7);
+        let bytes = encode(&model, format).unwrap();
+        let (header, decoded) = decode::<Person, _>(&bytes).unwrap();
+        assert_eq!(header, Header::new(format, 7));
+        assert_eq!(decoded, model);
+
+        // the optional fields round trip both empty and set
+        let mut model = model
+            .with_tenant(TenantId::new("acme").unwrap())
+            .with_ttl(chrono::Duration::seconds(60));
+        model.deleted_at = Some(model.version.updated_at);
+        model.status = Status::Active(StatusFlags::LOCKED);
+        let bytes = encode(&model, format).unwrap();
+        assert_eq!(decode::<Person, _>(&bytes).unwrap().1, model);
+
+        // the body is smaller than json
+        let json = encode(&model, Format::Json).unwrap();
+        assert!(bytes.len() < json.len());
+
+        // keys are validated on decode
+        struct Users;
+        impl KeyDomain for Users {
+            const PREFIX: &'static str = "usr";
+        }
+        assert!(matches!(
+            decode::<Person, DomainId<Users>>(&bytes),
+            Err(WireError::Decode(_))
+        ));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack() {
+        round_trip(Format::MessagePack);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor() {
+        round_trip(Format::Cbor);
+    }
+
+    #[cfg(feature = "bincode")]
+    #[test]
+    fn bincode() {
+        round_trip(Format::Bincode);
+    }
+
+    #[test]
+    fn unsupported() {
+        for format in Format::ALL.iter().filter(|f| !f.is_enabled()) {
+            assert_eq!(
+                encode(&person(), *format),
+                Err(WireError::Unsupported(*format))
+            );
+
+            let bytes = Header::new(*format, 1).to_bytes();
+            assert_eq!(
+                decode::<Person, RouteId>(&bytes).map(|_| ()),
+                Err(WireError::Unsupported(*format))
+            );
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use chrono::{Duration, Utc};
+use domain_keys::models::{PurgePolicy, Status, StatusFlags};
+use domain_keys::repository::{AsyncRepository, MemoryRepository, RepositoryError};
+use std::future::Future;
+use std::sync::Arc;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+// a minimal executor; the memory repository's futures are always ready
+fn block_on<F: Future>(future: F) -> F::Output {
+    fn raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            raw_waker()
+        }
+        fn noop(_: *const ()) {}
+
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(raw_waker()) };
+    let mut context = Context::from_waker(&waker);
+    let mut future = Box::pin(future);
+
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
+}
+
+#[test]
+fn async_round_trip() {
+    let repo: Arc<dyn AsyncRepository<String>> = Arc::new(MemoryRepository::new());
+
+    block_on(async {
+        let model = repo.insert("my value".to_string()).await.unwrap();
+        assert_eq!(repo.get(&model.key).await.unwrap(), Some(model.clone()));
+
+        let mut changed = model.clone();
+        changed.value = "new value".to_string();
+        let updated = repo.update(changed).await.unwrap();
+        assert_eq!(updated.version.update_count, 1);
+
+        match repo.update(model.clone()).await {
+            Err(RepositoryError::Conflict { .. }) => (),
+            other => panic!("should conflict: {:?}", other),
+        }
+
+        let deleted = repo.soft_delete(&model.key).await.unwrap();
+        assert!(deleted.is_deleted());
+        assert!(repo.list().await.unwrap().is_empty());
+        assert_eq!(repo.list_all().await.unwrap().len(), 1);
+
+        assert!(repo.delete(&model.key).await.unwrap().is_some());
+        assert_eq!(repo.get(&model.key).await.unwrap(), None);
+    });
+}
+
+#[test]
+fn async_soft_delete_restore_purge() {
+    let repo: MemoryRepository<String> = MemoryRepository::new();
+
+    block_on(async {
+        let model = repo.insert("my value".to_string()).await.unwrap();
+
+        let deleted = repo.soft_delete(&model.key).await.unwrap();
+        assert!(deleted.is_deleted());
+        assert_eq!(deleted.version.update_count, 1);
+
+        // deleting again is a no-op
+        let again = repo.soft_delete(&model.key).await.unwrap();
+        assert_eq!(again.version, deleted.version);
+
+        let restored = repo
+            .restore(&model.key, Status::Active(StatusFlags::NONE))
+            .await
+            .unwrap();
+        assert!(!restored.is_deleted());
+        assert_eq!(repo.list().await.unwrap(), vec![restored]);
+
+        repo.soft_delete(&model.key).await.unwrap();
+        let policy = PurgePolicy::new(Duration::days(30));
+        let now = Utc::now().naive_utc();
+
+        assert!(repo.purge(&policy, now).await.unwrap().is_empty());
+        let purged = repo.purge(&policy, now + Duration::days(31)).await.unwrap();
+        assert_eq!(purged.len(), 1);
+        assert!(repo.list_all().await.unwrap().is_empty());
+
+        match repo.soft_delete(&model.key).await {
+            Err(RepositoryError::NotFound(_)) => (),
+            other => panic!("should be missing: {:?}", other),
+        }
+    });
+}
+
+#[test]
+fn async_compare_and_swap_retries() {
+    let repo: MemoryRepository<u64> = MemoryRepository::new();
+
+    block_on(async {
+        let model = repo.insert(1).await.unwrap();
+
+        let swapped = repo.compare_and_swap(&model.key, 0, 10).await.unwrap();
+        assert_eq!(swapped.version.update_count, 1);
+
+        match repo.compare_and_swap(&model.key, 0, 20).await {
+            Err(RepositoryError::Conflict { .. }) => (),
+            other => panic!("should conflict: {:?}", other),
+        }
+
+        // a competing writer sneaks in on the first attempt
+        let mut calls = 0;
+        let updated = repo
+            .update_with(&model.key, 3, |current| {
+                calls += 1;
+                if calls == 1 {
+                    domain_keys::repository::Repository::compare_and_swap(
+                        &repo,
+                        &model.key,
+                        current.version.update_count,
+                        100,
+                    )
+                    .unwrap();
+                }
+                current.value + 1
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(calls, 2);
+        assert_eq!(updated.value, 101);
+        assert_eq!(updated.version.update_count, 3);
+
+        // every attempt conflicts
+        let err = repo
+            .update_with(&model.key, 2, |current| {
+                domain_keys::repository::Repository::compare_and_swap(
+                    &repo,
+                    &model.key,
+                    current.version.update_count,
+                    current.value + 10,
+                )
+                .unwrap();
+                current.value + 1
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RepositoryError::Conflict { .. }));
+    });
+}
+
+#[test]
+fn async_tasks_share_repository() {
+    let repo: Arc<MemoryRepository<u64>> = Arc::new(MemoryRepository::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|n| {
+            let repo = Arc::clone(&repo);
+            std::thread::spawn(move || {
+                block_on(async {
+                    for i in 0..25 {
+                        repo.insert(n * 100 + i).await.expect("should insert");
+                    }
+                })
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(block_on(repo.list_all()).unwrap().len(), 100);
+}
@@ -1,20 +1,18 @@
 use domain_keys::base62::Base62;
 
 fn generate_test_data() -> Vec<(u64, &'static str)> {
-    let mut tdata = vec![];
-
-    tdata.push((0, "0"));
-    tdata.push((1, "1"));
-    tdata.push((9, "9"));
-    tdata.push((10, "A"));
-    tdata.push((35, "Z"));
-    tdata.push((36, "a"));
-    tdata.push((61, "z"));
-    tdata.push((1_000, "G8"));
-    tdata.push((1_000_000, "4C92"));
-    tdata.push((u64::MAX, "LygHa16AHYF"));
-
-    tdata
+    vec![
+        (0, "0"),
+        (1, "1"),
+        (9, "9"),
+        (10, "A"),
+        (35, "Z"),
+        (36, "a"),
+        (61, "z"),
+        (1_000, "G8"),
+        (1_000_000, "4C92"),
+        (u64::MAX, "LygHa16AHYF"),
+    ]
 }
 
 #[test]
@@ -52,7 +50,7 @@ fn decode_empty_string() {
         Err(err) => println!("err: {:?}", err),
     }
 
-    assert!(Base62::decode(&"".to_string()).is_err());
+    assert!(Base62::decode("").is_err());
 }
 
 #[test]
@@ -63,6 +61,6 @@ fn decode_invalid_char() {
         Err(err) => println!("err: {:?}", err),
     }
 
-    assert!(Base62::decode(&"LLLL&GG".to_string()).is_err());
-    assert!(Base62::decode(&"-bad".to_string()).is_err());
+    assert!(Base62::decode("LLLL&GG").is_err());
+    assert!(Base62::decode("-bad").is_err());
 }
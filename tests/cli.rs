@@ -0,0 +1,137 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+const USAGE: i32 = 64;
+const DATA: i32 = 65;
+const NO_INPUT: i32 = 66;
+
+fn run(bin: &str, args: &[&str], input: Option<&str>) -> Output {
+    let mut child = Command::new(bin)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("should start binary");
+
+    if let Some(input) = input {
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+    }
+
+    child.wait_with_output().expect("should run binary")
+}
+
+fn base62(args: &[&str], input: Option<&str>) -> Output {
+    run(env!("CARGO_BIN_EXE_base62"), args, input)
+}
+
+#[test]
+fn base62_ok() {
+    let output = base62(&["-d", "G8"], None);
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1000\n");
+}
+
+#[test]
+fn base62_invalid_char() {
+    let output = base62(&["-d", "AB~CY"], None);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(DATA));
+    assert!(stderr.contains("at index 2"), "{}", stderr);
+}
+
+#[test]
+fn base62_usage() {
+    assert_eq!(base62(&[], None).status.code(), Some(USAGE));
+    assert_eq!(base62(&["--bogus"], None).status.code(), Some(USAGE));
+    assert_eq!(base62(&["--help"], None).status.code(), Some(0));
+
+    let output = base62(&["--encode", "1000", "--stream", "decode"], Some("G8\n"));
+    assert_eq!(output.status.code(), Some(USAGE));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn base62_stream() {
+    let output = base62(&["--stream", "decode"], Some("G8\n4C92\n"));
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1000\n1000000\n");
+
+    // one output row per input row, even for bad values
+    let output = base62(&["--stream", "decode"], Some("G8\n~\n4C92\n"));
+    assert_eq!(output.status.code(), Some(DATA));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1000\n\n1000000\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("stdin:2:"));
+
+    let output = base62(
+        &["--stream", "decode", "--column", "2"],
+        Some("a,G8\nb,~\nc,4C92\n"),
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "a,1000\nb,\nc,1000000\n"
+    );
+
+    let output = base62(&["--stream", "decode", "/no/such/file"], None);
+    assert_eq!(output.status.code(), Some(NO_INPUT));
+}
+
+#[test]
+fn show_route() {
+    let bin = env!("CARGO_BIN_EXE_show-route");
+
+    let output = run(bin, &["--routes", "25", "YM6I7clU96YvDTCr"], None);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "5\n");
+
+    let output = run(bin, &["--routes", "25", "~~"], None);
+    assert_eq!(output.status.code(), Some(DATA));
+
+    let output = run(bin, &[], None);
+    assert_eq!(output.status.code(), Some(USAGE));
+}
+
+#[test]
+fn key_binaries() {
+    let output = run(env!("CARGO_BIN_EXE_rtkey"), &["-a", "base36"], None);
+    assert!(output.status.success());
+
+    let output = run(env!("CARGO_BIN_EXE_txkey"), &["-a", "base64"], None);
+    assert_eq!(output.status.code(), Some(USAGE));
+}
+
+#[test]
+fn base62_encode() {
+    let output = base62(&["-e", "1000"], None);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "G8\n");
+
+    let output = base62(&["-e", "notanumber"], None);
+    assert_eq!(output.status.code(), Some(DATA));
+}
+
+#[test]
+fn base62_alphabet() {
+    let output = base62(&["-a", "base36", "-e", "12345"], None);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9ix\n");
+
+    let output = base62(&["--alphabet", "base36", "-d", "9IX"], None);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "12345\n");
+
+    let output = base62(
+        &["--alphabet", "base36", "--stream", "encode"],
+        Some("12345\n"),
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "9ix\n");
+
+    let output = base62(&["-a", "base64", "-e", "1"], None);
+    assert_eq!(output.status.code(), Some(USAGE));
+}
@@ -0,0 +1,121 @@
+use domain_keys::codec::{
+    convert, Codec, BASE36, BASE58, BASE62, BASE62_INVERTED, CODECS, CROCKFORD32,
+};
+use domain_keys::keys::{RouteKey, TimeStampKey};
+use std::collections::HashSet;
+
+fn generate_test_data() -> Vec<(u64, Codec, &'static str)> {
+    vec![
+        (0, BASE58, "1"),
+        (57, BASE58, "z"),
+        (58, BASE58, "21"),
+        (1_000_000, BASE58, "68GP"),
+        (0, BASE36, "0"),
+        (35, BASE36, "z"),
+        (1_000_000, BASE36, "lfls"),
+        (u64::MAX, BASE36, "3w5e11264sgsf"),
+        (31, CROCKFORD32, "Z"),
+        (32, CROCKFORD32, "10"),
+        (1_000_000, CROCKFORD32, "YGJ0"),
+        (u64::MAX, BASE62, "LygHa16AHYF"),
+    ]
+}
+
+#[test]
+fn encode() {
+    for (value, codec, expected) in generate_test_data() {
+        assert_eq!(codec.encode(value), expected, "{}", codec.name());
+    }
+}
+
+#[test]
+fn decode() {
+    for (expected, codec, value) in generate_test_data() {
+        let decoded = codec.decode(value).expect("should decode");
+        assert_eq!(decoded, expected, "{}", codec.name());
+    }
+}
+
+#[test]
+fn case_insensitive_decode() {
+    for codec in [BASE36, CROCKFORD32] {
+        for n in [0, 1_000, 1_000_000, u64::MAX] {
+            let encoded = codec.encode(n);
+            assert_eq!(codec.decode(&encoded.to_uppercase()).unwrap(), n);
+            assert_eq!(codec.decode(&encoded.to_lowercase()).unwrap(), n);
+        }
+    }
+}
+
+#[test]
+fn unique_route_keys() {
+    let max_tests: usize = 10_000;
+
+    for codec in CODECS {
+        let mut table = HashSet::with_capacity(max_tests);
+
+        for _ in 0..max_tests {
+            let key = RouteKey::create_with(&codec);
+            assert_eq!(key.len(), RouteKey::key_size(&codec));
+            assert!(table.insert(key));
+        }
+    }
+}
+
+#[test]
+fn timestamp_keys() {
+    for codec in CODECS {
+        let key = TimeStampKey::create_with(&codec);
+
+        assert_eq!(key.len(), TimeStampKey::key_size(&codec));
+        assert!(TimeStampKey::parse_timestamp_with(&key, &codec).is_ok());
+    }
+}
+
+// values cross-checked against libraries using the [0-9A-Za-z] and [0-9a-zA-Z] orderings
+fn generate_inverted_corpus() -> Vec<(u64, &'static str, &'static str)> {
+    vec![
+        (0, "0", "0"),
+        (9, "9", "9"),
+        (10, "A", "a"),
+        (35, "Z", "z"),
+        (36, "a", "A"),
+        (61, "z", "Z"),
+        (62, "10", "10"),
+        (1_000, "G8", "g8"),
+        (1_000_000, "4C92", "4c92"),
+        (14_673_204, "zZAa", "ZzaA"),
+        (34_441_886_726, "base62", "BASE62"),
+        (u64::MAX, "LygHa16AHYF", "lYGhA16ahyf"),
+    ]
+}
+
+#[test]
+fn inverted_corpus() {
+    for (value, standard, inverted) in generate_inverted_corpus() {
+        assert_eq!(BASE62.encode(value), standard);
+        assert_eq!(BASE62_INVERTED.encode(value), inverted);
+        assert_eq!(BASE62_INVERTED.decode(inverted).unwrap(), value);
+
+        assert_eq!(
+            convert(standard, &BASE62, &BASE62_INVERTED).unwrap(),
+            inverted
+        );
+        assert_eq!(
+            convert(inverted, &BASE62_INVERTED, &BASE62).unwrap(),
+            standard
+        );
+    }
+}
+
+#[test]
+fn convert_route_key() {
+    let key = RouteKey::create();
+    let inverted = convert(&key, &BASE62, &BASE62_INVERTED).unwrap();
+
+    assert_eq!(inverted.len(), key.len());
+    assert_eq!(
+        RouteKey::parse_timestamp(&key).unwrap(),
+        RouteKey::parse_timestamp_with(&inverted, &BASE62_INVERTED).unwrap()
+    );
+}
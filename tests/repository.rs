@@ -0,0 +1,120 @@
+use domain_keys::history::JsonLinesSink;
+use domain_keys::repository::{MemoryRepository, Repository, RepositoryError};
+use std::sync::Arc;
+use std::thread;
+
+#[test]
+fn shared_between_threads() {
+    let repo: Arc<MemoryRepository<u64>> = Arc::new(MemoryRepository::new());
+
+    let handles: Vec<_> = (0..4)
+        .map(|n| {
+            let repo = Arc::clone(&repo);
+            thread::spawn(move || {
+                for i in 0..250 {
+                    repo.insert(n * 1_000 + i).expect("should insert");
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(repo.len(), 1_000);
+    assert_eq!(repo.list().unwrap().len(), 1_000);
+}
+
+#[test]
+fn concurrent_updates_conflict() {
+    let repo: Arc<MemoryRepository<u64>> = Arc::new(MemoryRepository::new());
+    let model = repo.insert(0).unwrap();
+
+    let handles: Vec<_> = (1..=8)
+        .map(|n| {
+            let repo = Arc::clone(&repo);
+            let mut model = model.clone();
+            thread::spawn(move || {
+                model.value = n;
+                repo.update(model)
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+    let ok = results.iter().filter(|r| r.is_ok()).count();
+    let conflicts = results
+        .iter()
+        .filter(|r| matches!(r, Err(RepositoryError::Conflict { .. })))
+        .count();
+
+    // every writer read update count 0, so only one can win
+    assert_eq!(ok, 1);
+    assert_eq!(conflicts, 7);
+    assert_eq!(
+        repo.get(&model.key).unwrap().unwrap().version.update_count,
+        1
+    );
+}
+
+#[test]
+fn concurrent_counter_with_merge() {
+    let repo: Arc<MemoryRepository<u64>> = Arc::new(MemoryRepository::new());
+    let key = repo.insert(0).unwrap().key.to_string();
+
+    let handles: Vec<_> = (0..8)
+        .map(|_| {
+            let repo = Arc::clone(&repo);
+            let key = key.clone();
+            thread::spawn(move || {
+                for _ in 0..100 {
+                    repo.update_with(&key, usize::MAX, |current| current.value + 1)
+                        .expect("should eventually update");
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let model = repo.get(&key).unwrap().unwrap();
+    assert_eq!(model.value, 800);
+    assert_eq!(model.version.update_count, 800);
+}
+
+#[test]
+fn history_in_write_order() {
+    let path = std::env::temp_dir().join(format!("history-{}.jsonl", fastrand::u64(..)));
+    let sink = Arc::new(JsonLinesSink::open(&path).unwrap());
+    let repo: Arc<MemoryRepository<u64>> = Arc::new(MemoryRepository::new().with_sink(sink));
+    let model = repo.insert(0).unwrap();
+
+    let handles: Vec<_> = (0..4)
+        .map(|_| {
+            let repo = Arc::clone(&repo);
+            let key = model.key.to_string();
+            thread::spawn(move || {
+                for _ in 0..50 {
+                    repo.update_with(&key, 100, |current| current.value + 1)
+                        .expect("should update");
+                }
+            })
+        })
+        .collect();
+
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let events = JsonLinesSink::read(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(events.len(), 201);
+    for (count, event) in events.iter().enumerate() {
+        let version = event.new_version.as_ref().unwrap();
+        assert_eq!(version.update_count, count as u64);
+    }
+}
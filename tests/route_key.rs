@@ -11,7 +11,7 @@ fn unique_test() {
     for _ in 0..max_tests {
         let key = RouteKey::create();
         assert_eq!(key.len(), ROUTE_KEY_SIZE);
-        assert_eq!(table.insert(key), true);
+        assert!(table.insert(key));
     }
 
     assert_eq!(table.len(), max_tests);
@@ -9,7 +9,7 @@ fn unique_test() {
     for _ in 0..max_tests {
         let key = TimeStampKey::create();
         assert_eq!(key.len(), 12);
-        assert_eq!(table.insert(key), true);
+        assert!(table.insert(key));
     }
 
     assert_eq!(table.len(), max_tests);